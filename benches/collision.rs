@@ -0,0 +1,98 @@
+//! Benchmarks for the collision broad-phase and resolution systems.
+//!
+//! The game is a binary crate, so the collision modules are pulled in by path.
+//! Their test modules come along too, hence the relaxed lints.
+#![allow(dead_code, unused_imports)]
+
+use bevy::prelude::*;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+#[path = "../src/core/common.rs"]
+pub mod common;
+#[path = "../src/core/collision.rs"]
+pub mod collision;
+
+mod core {
+    pub use super::{collision, common};
+}
+
+use crate::core::{
+    collision::{detect_collisions, rebuild_spatial_grid, resolve_pairs, GridEntry, SpatialGrid},
+    common::{layers, Collider},
+};
+
+/// Old behaviour: every collider tested against every other one
+fn detect_collisions_all_pairs(mut query: Query<(Entity, &mut Transform, &Collider)>) {
+    let entries: Vec<GridEntry> = query
+        .iter()
+        .map(|(entity, t, c)| GridEntry {
+            entity,
+            position: t.translation,
+            radius: c.radius,
+            layer: c.layer,
+            mask: c.mask,
+            inverse_mass: 1.0,
+            sensor: false,
+        })
+        .collect();
+
+    let count = entries.len();
+    let pairs = (0..count).flat_map(|i| ((i + 1)..count).map(move |j| (i, j)));
+    let (positions, moved) = resolve_pairs(&entries, pairs);
+
+    for (i, entry) in entries.iter().enumerate().filter(|(i, _)| moved[*i]) {
+        if let Ok((_, mut t, _)) = query.get_mut(entry.entity) {
+            t.translation = positions[i];
+        }
+    }
+}
+
+/// Builds a world with `count` orc-sized colliders packed at roughly the density of a crowded fight
+fn setup_world(count: usize) -> World {
+    let mut world = World::new();
+    world.insert_resource(SpatialGrid::default());
+
+    let mut rng = StdRng::seed_from_u64(42);
+    let half_extent = (count as f32).sqrt() * 40.0;
+
+    for _ in 0..count {
+        let pos = Vec3::new(
+            rng.random_range(-half_extent..half_extent),
+            rng.random_range(-half_extent..half_extent),
+            0.0,
+        );
+        world.spawn((Transform::from_translation(pos), Collider {
+            radius: 22.0,
+            layer: layers::ENEMY,
+            mask: layers::ENEMY,
+        }));
+    }
+
+    world
+}
+
+fn bench_collisions(c: &mut Criterion) {
+    let mut group = c.benchmark_group("detect_collisions");
+
+    let mut grid = Schedule::default();
+    grid.add_systems((rebuild_spatial_grid, detect_collisions).chain());
+
+    let mut all_pairs = Schedule::default();
+    all_pairs.add_systems(detect_collisions_all_pairs);
+
+    for count in [1_000, 5_000] {
+        // Every sample starts from the same crowded layout, resolving it spreads the crowd out
+        group.bench_with_input(BenchmarkId::new("grid", count), &count, |b, &count| {
+            b.iter_batched(|| setup_world(count), |mut world| { grid.run(&mut world); world }, BatchSize::LargeInput);
+        });
+        group.bench_with_input(BenchmarkId::new("all_pairs", count), &count, |b, &count| {
+            b.iter_batched(|| setup_world(count), |mut world| { all_pairs.run(&mut world); world }, BatchSize::LargeInput);
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_collisions);
+criterion_main!(benches);
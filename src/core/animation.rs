@@ -23,7 +23,10 @@ fn animate_sprite(
             if let Some((_, _, indices)) = animation.set.animations.get(&animation.state) {
                 if let Some(atlas) = &mut sprite.texture_atlas {
                     if atlas.index >= indices.last {
-                        atlas.index = indices.first;
+                        // One-shot animations (like death) hold their last frame
+                        if animation.state.loops() {
+                            atlas.index = indices.first;
+                        }
                     } else {
                         atlas.index += 1;
                     }
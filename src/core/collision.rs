@@ -1,56 +1,706 @@
-use bevy::prelude::*;
-use crate::core::common::Collider;
+use bevy::{platform::collections::{HashMap, HashSet}, prelude::*};
+use crate::core::common::{Body, BodyKind, Collider, CollisionEnded, CollisionStarted, Sensor, StaticCollider};
 
 /// A plugin that handles collision
 pub struct CollisionPlugin;
 
 impl Plugin for CollisionPlugin {
     fn build(&self, app: &mut App) {
-        // Register the collision detection system in the Update schedule
-        app.add_systems(Update, detect_collisions);
+        // Register the broad-phase grids and the collision detection systems in the Update schedule
+        app.insert_resource(SpatialGrid::default())
+            .insert_resource(StaticGrid::default())
+            .insert_resource(Contacts::default())
+            .add_event::<CollisionStarted>()
+            .add_event::<CollisionEnded>()
+            .add_systems(Update, (
+                rebuild_spatial_grid,
+                rebuild_static_grid,
+                detect_contacts,
+                detect_collisions,
+                resolve_static_collisions,
+            ).chain());
     }
 }
 
-/// Detects and resolves collisions between entities with `Collider` and `Transform` components
-fn detect_collisions(mut query: Query<(Entity, &mut Transform, &Collider)>) {
-    // Collect entities data into a vector for comparison
-    let entities: Vec<(Entity, Vec3, f32)> = query
-        .iter()
-        .map(|(e, t, c)| (e, t.translation, c.radius))
-        .collect();
+/// Uniform-grid broad-phase, rebuilt every frame from all `Collider` entities.
+///
+/// Cells are at least as wide as the largest collider diameter, so every
+/// overlapping pair is guaranteed to sit in the same or in neighbouring cells.
+/// Sensors are kept out of the cells (a big interaction zone would blow up the
+/// cell size for everyone) and are matched against them in `sensor_pairs`.
+#[derive(Resource)]
+pub struct SpatialGrid {
+    /// Smallest allowed cell size
+    pub min_cell_size: f32,
+    /// Cell size used for the current frame
+    pub cell_size: f32,
+    /// Indices into `entries` for every occupied cell
+    pub cells: HashMap<IVec2, Vec<usize>>,
+    /// Collider data gathered during the last rebuild
+    pub entries: Vec<GridEntry>,
+}
+
+/// Single collider stored in the `SpatialGrid`
+#[derive(Clone, Copy)]
+pub struct GridEntry {
+    pub entity: Entity,
+    pub position: Vec3,
+    pub radius: f32,
+    pub layer: u32,
+    pub mask: u32,
+    /// 0 for kinematic and static bodies
+    pub inverse_mass: f32,
+    pub sensor: bool,
+}
+
+impl Default for SpatialGrid {
+    fn default() -> Self {
+        Self {
+            min_cell_size: 64.0,
+            cell_size: 64.0,
+            cells: HashMap::default(),
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl SpatialGrid {
+    /// Returns the cell containing the given world position
+    pub fn cell_of(&self, position: Vec3) -> IVec2 {
+        (position.truncate() / self.cell_size).floor().as_ivec2()
+    }
+
+    /// Clears the grid and inserts the given colliders
+    pub fn rebuild(&mut self, colliders: impl Iterator<Item = GridEntry>) {
+        self.cells.clear();
+        self.entries.clear();
+        self.entries.extend(colliders);
 
-    // Iterate over all pairs of entities
-    for i in 0..entities.len() {
-        let (e1, mut p1, r1) = entities[i];
-
-        for j in (i + 1)..entities.len() {
-            let (e2, mut p2, r2) = entities[j];
-
-            // Vector distance between entities
-            let delta: Vec3 = p2 - p1;
-            // Distanse between entities
-            let dist: f32 = delta.length();
-            // Minimum allowed distance
-            let min_dist: f32 = r1 + r2;
-
-            // Check for collision
-            if dist < min_dist {
-                // Colliders overlap
-                let overlap = min_dist - dist;
-                let dir = delta.normalize();
-
-                // Resolve collision by moving entities apart
-                p1 -= dir * (overlap * 0.5);
-                p2 += dir * (overlap * 0.5);
-
-                // Update the transforms with the new positions
-                if let Ok((_, mut t1, _)) = query.get_mut(e1) {
-                    t1.translation = p1;
+        // Make cells big enough for the largest collider
+        let max_radius = self
+            .entries
+            .iter()
+            .filter(|e| !e.sensor)
+            .fold(0.0_f32, |acc, e| acc.max(e.radius));
+        self.cell_size = self.min_cell_size.max(max_radius * 2.0);
+
+        for i in 0..self.entries.len() {
+            if self.entries[i].sensor {
+                continue;
+            }
+            let cell = self.cell_of(self.entries[i].position);
+            self.cells.entry(cell).or_default().push(i);
+        }
+    }
+
+    /// Calls `f` with the index of every entry in the cells overlapping the square of half size
+    /// `range` around `position`. It's up to `f` to check the exact distance.
+    pub fn for_each_within(&self, position: Vec3, range: f32, mut f: impl FnMut(usize)) {
+        let (min, max) = (self.cell_of(position - Vec3::splat(range)), self.cell_of(position + Vec3::splat(range)));
+
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                if let Some(indices) = self.cells.get(&IVec2::new(x, y)) {
+                    for &i in indices {
+                        f(i);
+                    }
                 }
-                if let Ok((_, mut t2, _)) = query.get_mut(e2) {
-                    t2.translation = p2;
+            }
+        }
+    }
+
+    /// Calls `f` with the index of every entry in the 3x3 block of cells around `position`
+    pub fn for_each_nearby(&self, position: Vec3, mut f: impl FnMut(usize)) {
+        let center = self.cell_of(position);
+
+        for x in -1..=1 {
+            for y in -1..=1 {
+                if let Some(indices) = self.cells.get(&(center + IVec2::new(x, y))) {
+                    for &i in indices {
+                        f(i);
+                    }
                 }
             }
         }
     }
+
+    /// Returns every pair of non-sensor entry indices `(i, j)` with `i < j` that may overlap
+    pub fn candidate_pairs(&self) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+
+        for i in 0..self.entries.len() {
+            if self.entries[i].sensor {
+                continue;
+            }
+            self.for_each_nearby(self.entries[i].position, |j| {
+                if i < j {
+                    pairs.push((i, j));
+                }
+            });
+        }
+
+        // Same order as a nested `for i { for j > i }` loop, see `resolve_pairs`
+        pairs.sort_unstable();
+        pairs
+    }
+
+    /// Returns every pair `(i, j)` with `i < j` of a sensor and a non-sensor entry that may overlap.
+    /// Sensors don't detect each other.
+    pub fn sensor_pairs(&self) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+
+        for (s, sensor) in self.entries.iter().enumerate().filter(|(_, e)| e.sensor) {
+            // Any collider reaching into the sensor has its center within this distance
+            let reach = Vec3::splat(sensor.radius + self.cell_size * 0.5);
+            let min = self.cell_of(sensor.position - reach);
+            let max = self.cell_of(sensor.position + reach);
+
+            for x in min.x..=max.x {
+                for y in min.y..=max.y {
+                    if let Some(indices) = self.cells.get(&IVec2::new(x, y)) {
+                        pairs.extend(indices.iter().map(|&i| (s.min(i), s.max(i))));
+                    }
+                }
+            }
+        }
+
+        pairs
+    }
+}
+
+/// Pairs of colliders that overlapped during the last frame, ordered `(min, max)`
+#[derive(Resource, Default)]
+pub struct Contacts {
+    pub pairs: HashSet<(Entity, Entity)>,
+}
+
+/// Size of a single `StaticGrid` cell
+const STATIC_CELL_SIZE: f32 = 128.0;
+
+/// Grid of static obstacles. Unlike `SpatialGrid` it is only rebuilt when obstacles
+/// are added or removed; an obstacle is stored in every cell its box touches.
+#[derive(Resource, Default)]
+pub struct StaticGrid {
+    pub cells: HashMap<IVec2, Vec<usize>>,
+    pub obstacles: Vec<Rect>,
+}
+
+impl StaticGrid {
+    /// Returns the range of cells covered by the given rectangle
+    fn cell_range(rect: Rect) -> (IVec2, IVec2) {
+        (
+            (rect.min / STATIC_CELL_SIZE).floor().as_ivec2(),
+            (rect.max / STATIC_CELL_SIZE).floor().as_ivec2(),
+        )
+    }
+
+    /// Clears the grid and inserts the given obstacles
+    pub fn rebuild(&mut self, obstacles: impl Iterator<Item = Rect>) {
+        self.cells.clear();
+        self.obstacles.clear();
+        self.obstacles.extend(obstacles);
+
+        for (i, rect) in self.obstacles.iter().enumerate() {
+            let (min, max) = Self::cell_range(*rect);
+            for x in min.x..=max.x {
+                for y in min.y..=max.y {
+                    self.cells.entry(IVec2::new(x, y)).or_default().push(i);
+                }
+            }
+        }
+    }
+
+    /// Returns the indices of every obstacle sharing a cell with the given rectangle
+    pub fn query(&self, rect: Rect) -> Vec<usize> {
+        let (min, max) = Self::cell_range(rect);
+        let mut found = Vec::new();
+
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                if let Some(indices) = self.cells.get(&IVec2::new(x, y)) {
+                    found.extend_from_slice(indices);
+                }
+            }
+        }
+
+        // Big obstacles span many cells
+        found.sort_unstable();
+        found.dedup();
+        found
+    }
+}
+
+/// Everything `rebuild_spatial_grid` reads from a collider
+type GridEntryData<'a> = (
+    Entity,
+    &'a Transform,
+    &'a GlobalTransform,
+    &'a Collider,
+    Option<&'a Body>,
+    Has<Sensor>,
+    Has<ChildOf>,
+);
+
+/// Collects all colliders into the `SpatialGrid`
+pub fn rebuild_spatial_grid(
+    mut grid: ResMut<SpatialGrid>,
+    query: Query<GridEntryData>,
+) {
+    grid.rebuild(query.iter().map(|(entity, t, global, c, body, sensor, is_child)| GridEntry {
+        entity,
+        // Sensors are often children of the entity they belong to. Top-level ones (like
+        // projectiles) use `Transform`, `GlobalTransform` is still at the origin on their first frame
+        position: if sensor && is_child { global.translation() } else { t.translation },
+        radius: c.radius,
+        layer: c.layer,
+        mask: c.mask,
+        inverse_mass: body.copied().unwrap_or_default().inverse_mass(),
+        sensor,
+    }));
+}
+
+/// Returns the pairs from `pairs` whose colliders interact and overlap
+pub fn overlapping_pairs(
+    entries: &[GridEntry],
+    pairs: impl IntoIterator<Item = (usize, usize)>,
+) -> Vec<(usize, usize)> {
+    pairs
+        .into_iter()
+        .filter(|&(i, j)| {
+            let (a, b) = (&entries[i], &entries[j]);
+            Collider::interacts(a.layer, a.mask, b.layer, b.mask)
+                && a.position.distance_squared(b.position) < (a.radius + b.radius).powi(2)
+        })
+        .collect()
+}
+
+/// Sends `CollisionStarted`/`CollisionEnded` by comparing this frame's overlaps with the last one.
+/// Runs before `detect_collisions`, so solid pairs pushed apart this frame still count as touching.
+pub fn detect_contacts(
+    grid: Res<SpatialGrid>,
+    mut contacts: ResMut<Contacts>,
+    mut started: EventWriter<CollisionStarted>,
+    mut ended: EventWriter<CollisionEnded>,
+) {
+    let pairs = grid.candidate_pairs().into_iter().chain(grid.sensor_pairs());
+    let current: HashSet<(Entity, Entity)> = overlapping_pairs(&grid.entries, pairs)
+        .into_iter()
+        .map(|(i, j)| {
+            let (a, b) = (grid.entries[i].entity, grid.entries[j].entity);
+            (a.min(b), a.max(b))
+        })
+        .collect();
+
+    for &(a, b) in current.difference(&contacts.pairs) {
+        started.write(CollisionStarted { a, b });
+    }
+    for &(a, b) in contacts.pairs.difference(&current) {
+        ended.write(CollisionEnded { a, b });
+    }
+
+    contacts.pairs = current;
+}
+
+/// Pushes overlapping colliders apart, visiting `pairs` in nested loop order (sorted by `i`,
+/// then `j`). The overlap is split by inverse mass (half each for equal masses); a pair of
+/// unpushable bodies is skipped.
+///
+/// Works like the all-pairs loop it replaced: the first entity of a pair keeps its pushes
+/// while its `i` lasts, the second one is pushed from its position at the start of the pass,
+/// and the last write to an entity wins. Returns the new positions and which entries were moved.
+pub fn resolve_pairs(
+    entries: &[GridEntry],
+    pairs: impl IntoIterator<Item = (usize, usize)>,
+) -> (Vec<Vec3>, Vec<bool>) {
+    // Positions at the start of the pass, and the ones written out
+    let snapshot: Vec<Vec3> = entries.iter().map(|e| e.position).collect();
+    let mut positions = snapshot.clone();
+    let mut moved = vec![false; positions.len()];
+    // Running position of the current first entity
+    let mut current: Option<(usize, Vec3)> = None;
+
+    for (i, j) in pairs {
+        let (a, b) = (&entries[i], &entries[j]);
+
+        // Skip pairs whose layers and masks don't match, sensors never push
+        if !Collider::interacts(a.layer, a.mask, b.layer, b.mask) || a.sensor || b.sensor {
+            continue;
+        }
+
+        // Neither of them can be pushed
+        let total_inverse_mass = a.inverse_mass + b.inverse_mass;
+        if total_inverse_mass <= 0.0 {
+            continue;
+        }
+
+        let mut p1 = match current {
+            Some((index, p1)) if index == i => p1,
+            _ => snapshot[i],
+        };
+        let mut p2 = snapshot[j];
+
+        // Vector distance between entities
+        let delta: Vec3 = p2 - p1;
+        // Distanse between entities
+        let dist: f32 = delta.length();
+        // Minimum allowed distance
+        let min_dist: f32 = a.radius + b.radius;
+
+        // Check for collision
+        if dist < min_dist {
+            // Colliders overlap
+            let overlap = min_dist - dist;
+            // Entities on the exact same spot get pushed apart along X
+            let dir = delta.try_normalize().unwrap_or(Vec3::X);
+
+            // Resolve collision by moving entities apart, lighter ones move more
+            let share_a = a.inverse_mass / total_inverse_mass;
+            let share_b = b.inverse_mass / total_inverse_mass;
+            p1 -= dir * (overlap * share_a);
+            p2 += dir * (overlap * share_b);
+
+            // Update the positions, unpushable ones are never written
+            if share_a > 0.0 {
+                positions[i] = p1;
+                moved[i] = true;
+            }
+            if share_b > 0.0 {
+                positions[j] = p2;
+                moved[j] = true;
+            }
+        }
+        current = Some((i, p1));
+    }
+
+    (positions, moved)
+}
+
+/// Detects and resolves collisions between entities with `Collider` and `Transform` components
+pub fn detect_collisions(grid: Res<SpatialGrid>, mut query: Query<&mut Transform, With<Collider>>) {
+    // Only check pairs that share or neighbour a grid cell
+    let (positions, moved) = resolve_pairs(&grid.entries, grid.candidate_pairs());
+
+    // Update the transforms with the new positions
+    for (i, entry) in grid.entries.iter().enumerate().filter(|(i, _)| moved[*i]) {
+        if let Ok(mut t) = query.get_mut(entry.entity) {
+            t.translation = positions[i];
+        }
+    }
+}
+
+/// Rebuilds the `StaticGrid` whenever a `StaticCollider` is added or removed
+pub fn rebuild_static_grid(
+    mut grid: ResMut<StaticGrid>,
+    query: Query<(&Transform, &StaticCollider)>,
+    added: Query<(), Added<StaticCollider>>,
+    mut removed: RemovedComponents<StaticCollider>,
+) {
+    // Static obstacles rarely change, skip the rebuild if nothing happened.
+    // Removals are read every frame, so they don't pile up while obstacles are being added
+    let removed_any = removed.read().count() > 0;
+    if added.is_empty() && !removed_any {
+        return;
+    }
+
+    grid.rebuild(query.iter().map(|(t, s)| {
+        Rect::from_center_half_size(t.translation.truncate(), s.half_size)
+    }));
+}
+
+/// Colliders that static obstacles can push
+type PushedByObstacles = (Without<StaticCollider>, Without<Sensor>);
+
+/// Pushes dynamic colliders out of static obstacles
+pub fn resolve_static_collisions(
+    grid: Res<StaticGrid>,
+    mut query: Query<(&mut Transform, &Collider, Option<&Body>), PushedByObstacles>,
+) {
+    if grid.obstacles.is_empty() {
+        return;
+    }
+
+    for (mut transform, collider, body) in query.iter_mut() {
+        // Static bodies stay where they were placed
+        if body.is_some_and(|b| b.kind == BodyKind::Static) {
+            continue;
+        }
+
+        let mut center = transform.translation.truncate();
+        let bounds = Rect::from_center_half_size(center, Vec2::splat(collider.radius));
+
+        for i in grid.query(bounds) {
+            if let Some(push) = circle_rect_push(center, collider.radius, grid.obstacles[i]) {
+                center += push;
+            }
+        }
+
+        // Only the XY plane is affected, Z keeps its draw order
+        if center != transform.translation.truncate() {
+            transform.translation.x = center.x;
+            transform.translation.y = center.y;
+        }
+    }
+}
+
+/// Returns the offset needed to move a circle out of a rectangle, if they overlap
+pub fn circle_rect_push(center: Vec2, radius: f32, rect: Rect) -> Option<Vec2> {
+    // Closest point of the rectangle to the circle
+    let closest = center.clamp(rect.min, rect.max);
+    let delta = center - closest;
+    let dist_sq = delta.length_squared();
+
+    if dist_sq >= radius * radius {
+        return None;
+    }
+
+    if dist_sq > 0.0 {
+        // Center outside of the box, push along the contact normal
+        let dist = dist_sq.sqrt();
+        return Some(delta / dist * (radius - dist));
+    }
+
+    // Center inside of the box, push out through the nearest side
+    let to_min = center - rect.min;
+    let to_max = rect.max - center;
+    let exits = [
+        (to_min.x, Vec2::NEG_X),
+        (to_max.x, Vec2::X),
+        (to_min.y, Vec2::NEG_Y),
+        (to_max.y, Vec2::Y),
+    ];
+    let (depth, normal) = exits
+        .into_iter()
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .unwrap_or((0.0, Vec2::X));
+
+    Some(normal * (depth + radius))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::common::layers;
+
+    /// Collider of radius 10 at the given position
+    fn entry(id: u32, position: Vec3, layer: u32, mask: u32) -> GridEntry {
+        GridEntry { entity: Entity::from_raw(id), position, radius: 10.0, layer, mask, inverse_mass: 1.0, sensor: false }
+    }
+
+    /// Every `(i, j)` pair with `i < j`, in nested loop order
+    fn all_pairs(count: usize) -> Vec<(usize, usize)> {
+        (0..count).flat_map(|i| ((i + 1)..count).map(move |j| (i, j))).collect()
+    }
+
+    /// The all-pairs loop `detect_collisions` used before the grid, on plain positions
+    fn all_pairs_loop(entities: &[(Vec3, f32)]) -> Vec<Vec3> {
+        let mut transforms: Vec<Vec3> = entities.iter().map(|(p, _)| *p).collect();
+
+        for i in 0..entities.len() {
+            let (mut p1, r1) = entities[i];
+
+            for j in (i + 1)..entities.len() {
+                let (mut p2, r2) = entities[j];
+
+                let delta: Vec3 = p2 - p1;
+                let dist: f32 = delta.length();
+                let min_dist: f32 = r1 + r2;
+
+                if dist < min_dist {
+                    let overlap = min_dist - dist;
+                    let dir = delta.normalize();
+
+                    p1 -= dir * (overlap * 0.5);
+                    p2 += dir * (overlap * 0.5);
+
+                    transforms[i] = p1;
+                    transforms[j] = p2;
+                }
+            }
+        }
+        transforms
+    }
+
+    #[test]
+    fn grid_matches_brute_force() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut grid = SpatialGrid::default();
+
+        // Loosely packed crowd of orc-sized colliders
+        grid.rebuild((0..300).map(|i| GridEntry {
+            entity: Entity::from_raw(i),
+            position: Vec3::new(rng.random_range(-800.0..800.0), rng.random_range(-800.0..800.0), 0.0),
+            radius: 22.0,
+            layer: layers::ENEMY,
+            mask: layers::ENEMY,
+            inverse_mass: 1.0,
+            sensor: false,
+        }));
+
+        let (grid_pos, grid_moved) = resolve_pairs(&grid.entries, grid.candidate_pairs());
+        let old_pos = all_pairs_loop(&grid.entries.iter().map(|e| (e.position, e.radius)).collect::<Vec<_>>());
+
+        assert!(grid_moved.iter().any(|m| *m));
+        for (a, b) in grid_pos.iter().zip(&old_pos) {
+            assert!(a.distance(*b) < 1e-4, "{a} != {b}");
+        }
+    }
+
+    #[test]
+    fn range_query_finds_everything_in_range() {
+        let mut grid = SpatialGrid::default();
+        grid.rebuild((0..40).map(|i| entry(i, Vec3::new(i as f32 * 25.0, 0.0, 0.0), layers::ENEMY, layers::ENEMY)));
+
+        let mut found = Vec::new();
+        grid.for_each_within(Vec3::new(500.0, 0.0, 0.0), 200.0, |i| found.push(i));
+
+        // Everything within 200, and nothing beyond the cells around that
+        assert!((12..=28).all(|i| found.contains(&i)));
+        assert!(found.iter().all(|&i| (grid.entries[i].position.x - 500.0).abs() < 200.0 + 2.0 * grid.cell_size));
+    }
+
+    #[test]
+    fn overlapping_pair_is_split_evenly() {
+        let entries = [
+            entry(0, Vec3::ZERO, layers::ENEMY, layers::ENEMY),
+            entry(1, Vec3::new(10.0, 0.0, 0.0), layers::ENEMY, layers::ENEMY),
+        ];
+
+        let (positions, moved) = resolve_pairs(&entries, [(0, 1)]);
+
+        assert_eq!(moved, vec![true, true]);
+        assert_eq!(positions[0], Vec3::new(-5.0, 0.0, 0.0));
+        assert_eq!(positions[1], Vec3::new(15.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn overlap_is_split_by_inverse_mass() {
+        let mut light = entry(0, Vec3::ZERO, layers::ENEMY, layers::ENEMY);
+        let mut heavy = entry(1, Vec3::new(10.0, 0.0, 0.0), layers::ENEMY, layers::ENEMY);
+        light.inverse_mass = 1.0;
+        heavy.inverse_mass = 1.0 / 3.0;
+
+        let (positions, _) = resolve_pairs(&[light, heavy], [(0, 1)]);
+
+        // 10 units of overlap, the light one takes 3/4 of it
+        assert!(positions[0].distance(Vec3::new(-7.5, 0.0, 0.0)) < 1e-4);
+        assert!(positions[1].distance(Vec3::new(12.5, 0.0, 0.0)) < 1e-4);
+    }
+
+    #[test]
+    fn immovable_bodies_are_never_pushed() {
+        let mut wall = entry(0, Vec3::ZERO, layers::NPC, layers::ENEMY);
+        wall.inverse_mass = 0.0;
+        let orc = entry(1, Vec3::new(10.0, 0.0, 0.0), layers::ENEMY, layers::NPC);
+
+        let (positions, moved) = resolve_pairs(&[wall, orc], [(0, 1)]);
+        assert_eq!(moved, vec![false, true]);
+        assert_eq!(positions[0], Vec3::ZERO);
+        assert_eq!(positions[1], Vec3::new(20.0, 0.0, 0.0));
+
+        // Two immovable bodies just stay overlapped
+        let mut other = wall;
+        other.position = Vec3::new(5.0, 0.0, 0.0);
+        assert_eq!(resolve_pairs(&[wall, other], [(0, 1)]).1, vec![false, false]);
+    }
+
+    #[test]
+    fn big_sensor_reports_overlaps_without_pushing() {
+        let mut zone = entry(0, Vec3::ZERO, layers::NPC, layers::PLAYER);
+        zone.radius = 150.0;
+        zone.sensor = true;
+        let player = entry(1, Vec3::new(155.0, 0.0, 0.0), layers::PLAYER, layers::NPC);
+        let far = entry(2, Vec3::new(175.0, 0.0, 0.0), layers::PLAYER, layers::NPC);
+        let orc = entry(3, Vec3::new(20.0, 0.0, 0.0), layers::ENEMY, layers::ENEMY);
+
+        let mut grid = SpatialGrid::default();
+        grid.rebuild([zone, player, far, orc].into_iter());
+
+        // The sensor doesn't inflate the cells, but is still matched across several of them
+        assert_eq!(grid.cell_size, 64.0);
+        assert!(grid.candidate_pairs().iter().all(|&(i, _)| i != 0));
+        assert_eq!(overlapping_pairs(&grid.entries, grid.sensor_pairs()), vec![(0, 1)]);
+
+        let (positions, moved) = resolve_pairs(&grid.entries, all_pairs(grid.entries.len()));
+        assert_eq!(moved, vec![false; 4]);
+        assert_eq!(positions[1], player.position);
+    }
+
+    #[test]
+    fn masks_filter_pairs() {
+        let soldiers = [
+            entry(0, Vec3::ZERO, layers::MINION, layers::ENEMY),
+            entry(1, Vec3::new(10.0, 0.0, 0.0), layers::MINION, layers::ENEMY),
+        ];
+        let soldier_and_orc = [
+            entry(0, Vec3::ZERO, layers::MINION, layers::ENEMY),
+            entry(1, Vec3::new(10.0, 0.0, 0.0), layers::ENEMY, layers::MINION),
+        ];
+        // Mask only on one side is not enough
+        let one_sided = [
+            entry(0, Vec3::ZERO, layers::PLAYER, layers::NPC),
+            entry(1, Vec3::new(10.0, 0.0, 0.0), layers::NPC, layers::MINION),
+        ];
+
+        assert_eq!(resolve_pairs(&soldiers, [(0, 1)]).1, vec![false, false]);
+        assert_eq!(resolve_pairs(&soldier_and_orc, [(0, 1)]).1, vec![true, true]);
+        assert_eq!(resolve_pairs(&one_sided, [(0, 1)]).1, vec![false, false]);
+    }
+
+    #[test]
+    fn circle_outside_box_is_left_alone() {
+        let rect = Rect::new(0.0, 0.0, 100.0, 100.0);
+
+        assert_eq!(circle_rect_push(Vec2::new(150.0, 50.0), 20.0, rect), None);
+        // Touching exactly is not an overlap
+        assert_eq!(circle_rect_push(Vec2::new(120.0, 50.0), 20.0, rect), None);
+    }
+
+    #[test]
+    fn circle_touching_side_is_pushed_along_normal() {
+        let rect = Rect::new(0.0, 0.0, 100.0, 100.0);
+
+        let push = circle_rect_push(Vec2::new(110.0, 50.0), 20.0, rect).unwrap();
+
+        assert!(push.distance(Vec2::new(10.0, 0.0)) < 1e-4);
+    }
+
+    #[test]
+    fn circle_touching_corner_is_pushed_diagonally() {
+        let rect = Rect::new(0.0, 0.0, 100.0, 100.0);
+        let center = Vec2::new(110.0, 110.0);
+
+        let push = circle_rect_push(center, 20.0, rect).unwrap();
+
+        // Ends up exactly one radius away from the corner
+        assert!(((center + push).distance(Vec2::new(100.0, 100.0)) - 20.0).abs() < 1e-4);
+        assert!((push.x - push.y).abs() < 1e-4);
+    }
+
+    #[test]
+    fn circle_inside_box_exits_through_nearest_side() {
+        let rect = Rect::new(0.0, 0.0, 100.0, 100.0);
+
+        let push = circle_rect_push(Vec2::new(50.0, 90.0), 20.0, rect).unwrap();
+
+        // 10 to reach the top side plus the radius
+        assert!(push.distance(Vec2::new(0.0, 30.0)) < 1e-4);
+    }
+
+    #[test]
+    fn static_grid_finds_obstacles_spanning_many_cells() {
+        let mut grid = StaticGrid::default();
+        let wall = Rect::new(-1000.0, -10.0, 1000.0, 10.0);
+        let rock = Rect::new(500.0, 500.0, 520.0, 520.0);
+        grid.rebuild([wall, rock].into_iter());
+
+        // Wall is found from both of its far ends, once each
+        assert_eq!(grid.query(Rect::from_center_half_size(Vec2::new(-900.0, 0.0), Vec2::splat(20.0))), vec![0]);
+        assert_eq!(grid.query(Rect::from_center_half_size(Vec2::new(900.0, 0.0), Vec2::splat(20.0))), vec![0]);
+        // Query covering several wall cells reports it only once
+        assert_eq!(grid.query(Rect::new(-400.0, -20.0, 400.0, 20.0)), vec![0]);
+        assert_eq!(grid.query(Rect::from_center_half_size(Vec2::new(510.0, 510.0), Vec2::splat(5.0))), vec![1]);
+        assert!(grid.query(Rect::from_center_half_size(Vec2::new(0.0, 700.0), Vec2::splat(5.0))).is_empty());
+    }
 }
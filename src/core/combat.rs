@@ -1,27 +1,238 @@
-use bevy::prelude::*;
-use crate::core::common::{HitReactionTimer, Stats, AttackEvent};
+use bevy::{platform::collections::HashMap, prelude::*};
+use crate::core::common::{
+    Animation, AnimationState, ApplyStatus, Body, Collider, Critical, DamageDealt, DamageType, Defense, Died, Dying, Faction, FactionTable, GameState, HitReactionTimer,
+    InvincibilityTimer, Knockback, MoveTo, Player, Stats, AttackEvent, Target, Velocity,
+};
+use crate::core::hitbox::Hurtbox;
+use crate::core::steering::Steering;
+
+/// How long a corpse stays on the ground (death animation included)
+const CORPSE_SECONDS: f32 = 3.0;
 
 /// Plugin responsible for handling combat-related systems, like applying damage
 pub struct CombatPlugin;
 
 impl Plugin for CombatPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, handle_attack_events);
+        app.add_event::<DamageDealt>()
+            .add_event::<Died>()
+            .init_state::<GameState>()
+            .init_resource::<FactionTable>()
+            .add_systems(Update, (tick_invincibility, handle_attack_events, detect_deaths, remove_corpses).chain());
+    }
+}
+
+/// Everything `handle_attack_events` needs from the target
+type AttackTarget<'a> = (
+    &'a mut Stats,
+    &'a mut HitReactionTimer,
+    Option<&'a Defense>,
+    Option<&'a mut InvincibilityTimer>,
+    Option<&'a mut Velocity>,
+    Option<&'a Body>,
+    Option<&'a Faction>,
+);
+
+/// Entities that can still die
+type Living<'a> = (Entity, &'a Stats, Option<&'a mut Animation>, Has<Player>);
+
+/// Counts down the i-frames of entities that were hit recently
+fn tick_invincibility(
+    mut query: Query<&mut InvincibilityTimer>,
+    time: Res<Time>,
+) {
+    for mut invincibility in query.iter_mut() {
+        invincibility.timer.tick(time.delta());
     }
 }
 
-/// System that processes `AttackEvent`s and reduces the HP of the targeted entity
-fn handle_attack_events(
+/// System that processes `AttackEvent`s, runs them through the damage pipeline
+/// (crits, armor, resistances) and reduces the HP of the targeted entity.
+/// Hits on an entity with running i-frames are ignored, a landed hit starts them.
+/// Dying targets and ones the `FactionTable` doesn't set against the attacker aren't hurt.
+pub fn handle_attack_events(
     mut events: EventReader<AttackEvent>,   // Reads all attack events for the current frame
-    mut query: Query<(&mut Stats, &mut HitReactionTimer)>,           // Query to access the mutable stats of entities
+    mut query: Query<AttackTarget, Without<Dying>>, // Query to access the mutable stats of entities
+    q_attacker: Query<(Option<&Critical>, Option<&Knockback>, Option<&Faction>)>,
+    q_positions: Query<&Transform>,
+    factions: Res<FactionTable>,
+    mut dealt: EventWriter<DamageDealt>,
+    mut statuses: EventWriter<ApplyStatus>,
 ) {
     // Iterate over all attack events triggered this frame
     for event in events.read() {
         // Attempt to get the target's Stats component using the entity ID from the event
-        if let Ok((mut target_stats, mut reaction_timer)) = query.get_mut(event.target) {
+        if let Ok((mut target_stats, mut reaction_timer, defense, invincibility, velocity, body, target_faction)) = query.get_mut(event.target) {
+            let (critical, knockback, attacker_faction) = q_attacker.get(event.attacker).unwrap_or((None, None, None));
+
+            // Hits of attackers that are gone (like the owner of a projectile) were checked where they were made
+            let friendly = matches!(
+                (attacker_faction, target_faction),
+                (Some(attacker), Some(target)) if !factions.is_hostile(*attacker, *target)
+            );
+            if friendly {
+                continue;
+            }
+
+            // Still invincible from an earlier hit (possibly from this very frame)
+            if let Some(mut invincibility) = invincibility {
+                if !invincibility.timer.finished() {
+                    continue;
+                }
+                invincibility.timer.reset();
+            }
+
+            // Roll for a critical hit if the attacker can land them
+            let crit_multiplier = critical
+                .filter(|crit| rand::random::<f32>() < crit.chance)
+                .map(|crit| crit.multiplier);
+
+            let amount = final_damage(event.damage, event.damage_type, defense, crit_multiplier);
+
             // Apply the damage by subtracting from current HP
-            target_stats.hp -= event.damage;
-            reaction_timer.timer.reset()
+            target_stats.hp -= amount;
+            reaction_timer.timer.reset();
+
+            // Shove the target away from the attacker, heavier bodies move less
+            let positions = (q_positions.get(event.attacker), q_positions.get(event.target));
+            if let (Some(knockback), Some(mut velocity), (Ok(from), Ok(to))) = (knockback, velocity, positions) {
+                let inverse_mass = body.copied().unwrap_or_default().inverse_mass();
+                velocity.0 += knockback_impulse(from.translation, to.translation, knockback.impulse, inverse_mass);
+            }
+
+            dealt.write(DamageDealt {
+                attacker: event.attacker,
+                target: event.target,
+                amount,
+                damage_type: event.damage_type,
+                critical: crit_multiplier.is_some(),
+            });
+
+            // Lasting effects carried by the hit
+            for effect in &event.effects {
+                statuses.write(ApplyStatus { target: event.target, source: event.attacker, effect: *effect });
+            }
+        }
+    }
+}
+
+/// Turns every entity whose HP dropped to 0 into a dying one: sends `Died`, starts the
+/// death animation and takes it out of the fight. Player death ends the game.
+pub fn detect_deaths(
+    mut commands: Commands,
+    mut dealt: EventReader<DamageDealt>,
+    mut query: Query<Living, Without<Dying>>,
+    mut died: EventWriter<Died>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    // Last attacker of everyone hit this frame
+    let killers: HashMap<Entity, Entity> = dealt.read().map(|event| (event.target, event.attacker)).collect();
+
+    for (entity, stats, animation, is_player) in query.iter_mut() {
+        if stats.hp > 0 {
+            continue;
+        }
+
+        died.write(Died { entity, killer: killers.get(&entity).copied() });
+
+        if let Some(mut animation) = animation {
+            animation.state = AnimationState::Death;
+        }
+
+        // Corpses don't block, can't be hit and stop chasing things. Without `Steering`
+        // whatever velocity is left fades out
+        commands
+            .entity(entity)
+            .insert(Dying { timer: Timer::from_seconds(CORPSE_SECONDS, TimerMode::Once) })
+            .remove::<(Collider, Hurtbox, Target, MoveTo, Steering)>();
+
+        if is_player {
+            next_state.set(GameState::GameOver);
         }
     }
 }
+
+/// Despawns corpses once their timer runs out. The player's body stays for the game over screen
+fn remove_corpses(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Dying), Without<Player>>,
+    time: Res<Time>,
+) {
+    for (entity, mut dying) in query.iter_mut() {
+        if dying.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Damage left after the crit multiplier, armor (physical only) and resistances are applied.
+/// Never negative, rounded to the nearest point.
+pub fn final_damage(base: i32, damage_type: DamageType, defense: Option<&Defense>, crit_multiplier: Option<f32>) -> i32 {
+    let mut amount = base as f32 * crit_multiplier.unwrap_or(1.0);
+
+    if let Some(defense) = defense {
+        if damage_type == DamageType::Physical {
+            amount *= 100.0 / (100.0 + defense.armor.max(0) as f32);
+        }
+        let resistance = defense.resistances.get(&damage_type).copied().unwrap_or(0.0);
+        amount *= 1.0 - resistance.min(1.0);
+    }
+
+    (amount.round() as i32).max(0)
+}
+
+/// Velocity change pushing the target directly away from the attacker on the XY plane
+pub fn knockback_impulse(attacker: Vec3, target: Vec3, impulse: f32, inverse_mass: f32) -> Vec3 {
+    let away = (target - attacker).truncate().normalize_or_zero();
+    (away * impulse * inverse_mass).extend(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::platform::collections::HashMap;
+
+    #[test]
+    fn knockback_pushes_away_and_scales_with_mass() {
+        let attacker = Vec3::new(0.0, 0.0, 5.0);
+        let target = Vec3::new(0.0, -30.0, 0.0);
+
+        assert_eq!(knockback_impulse(attacker, target, 600.0, 1.0), Vec3::new(0.0, -600.0, 0.0));
+        assert_eq!(knockback_impulse(attacker, target, 600.0, 0.25), Vec3::new(0.0, -150.0, 0.0));
+        // Immovable bodies stay put
+        assert_eq!(knockback_impulse(attacker, target, 600.0, 0.0), Vec3::ZERO);
+    }
+
+    #[test]
+    fn undefended_damage_passes_through() {
+        assert_eq!(final_damage(30, DamageType::Physical, None, None), 30);
+        assert_eq!(final_damage(30, DamageType::Physical, None, Some(2.0)), 60);
+    }
+
+    #[test]
+    fn armor_only_reduces_physical_damage() {
+        let defense = Defense { armor: 100, resistances: HashMap::default() };
+
+        assert_eq!(final_damage(30, DamageType::Physical, Some(&defense), None), 15);
+        assert_eq!(final_damage(30, DamageType::Fire, Some(&defense), None), 30);
+    }
+
+    #[test]
+    fn resistances_stack_with_armor_and_crits() {
+        let defense = Defense {
+            armor: 25,
+            resistances: HashMap::from([
+                (DamageType::Physical, 0.5),
+                (DamageType::Fire, 1.5),
+                (DamageType::Poison, -0.5),
+            ]),
+        };
+
+        // 30 * 2 crit * 0.8 armor * 0.5 resistance
+        assert_eq!(final_damage(30, DamageType::Physical, Some(&defense), Some(2.0)), 24);
+        // Over 100% resistance doesn't heal
+        assert_eq!(final_damage(30, DamageType::Fire, Some(&defense), None), 0);
+        // Weakness adds damage
+        assert_eq!(final_damage(30, DamageType::Poison, Some(&defense), None), 45);
+    }
+}
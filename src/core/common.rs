@@ -1,8 +1,13 @@
-use bevy::{platform::collections::HashMap, prelude::*};
+use bevy::{platform::collections::{HashMap, HashSet}, prelude::*};
 
 
-enum MinionType {
-    Soldier
+/// Kinds of minnions the player can recruit, see `world::minnions::unit` for what sets them apart
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MinionType {
+    Soldier,
+    Archer,
+    ShieldBearer,
+    Healer,
 }
 
 #[derive(Clone)]
@@ -16,9 +21,131 @@ pub struct Item {
 pub struct Velocity(pub Vec3);
 
 
+/// Side an entity fights for. Whether two sides fight is decided by the `FactionTable`
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Faction {
+    /// The player and their soldiers
+    Player,
+    Orcs,
+    /// Animals, attack anyone who comes close
+    Wildlife,
+    /// Shopkeepers and other NPCs, never fight
+    Neutral,
+}
+
+/// Which factions are hostile to each other. Hostility is symmetric
+#[derive(Resource)]
+pub struct FactionTable {
+    hostile: HashSet<(Faction, Faction)>,
+}
+
+impl Default for FactionTable {
+    fn default() -> Self {
+        let mut table = Self { hostile: HashSet::default() };
+        table.set_hostile(Faction::Player, Faction::Orcs, true);
+        table.set_hostile(Faction::Wildlife, Faction::Player, true);
+        table.set_hostile(Faction::Wildlife, Faction::Orcs, true);
+        table
+    }
+}
+
+impl FactionTable {
+    fn key(a: Faction, b: Faction) -> (Faction, Faction) {
+        if (a as u8) <= (b as u8) { (a, b) } else { (b, a) }
+    }
+
+    pub fn is_hostile(&self, a: Faction, b: Faction) -> bool {
+        self.hostile.contains(&Self::key(a, b))
+    }
+
+    /// Makes two factions (or a faction with itself, for infighting) hostile or friendly
+    pub fn set_hostile(&mut self, a: Faction, b: Faction, hostile: bool) {
+        if hostile {
+            self.hostile.insert(Self::key(a, b));
+        } else {
+            self.hostile.remove(&Self::key(a, b));
+        }
+    }
+}
+
+
+/// Collision layer bits for `Collider::layer` and `Collider::mask`.
+/// Layers decide who blocks whom, not who fights whom: NPC bodies block everyone without
+/// being hostile, orcs shove each other without infighting. Contacts only turn into damage
+/// after the `FactionTable` is asked, see `projectile_hits` and `resolve_hitboxes`.
+pub mod layers {
+    pub const PLAYER: u32 = 1 << 0;
+    pub const MINION: u32 = 1 << 1;
+    pub const ENEMY: u32 = 1 << 2;
+    pub const NPC: u32 = 1 << 3;
+}
+
+
 #[derive(Component)]
 pub struct Collider {
     pub radius: f32,
+    /// Layers this collider belongs to
+    pub layer: u32,
+    /// Layers this collider collides with
+    pub mask: u32,
+}
+
+impl Collider {
+    /// Two colliders interact only if each one's mask contains the other's layer
+    pub fn interacts(layer_a: u32, mask_a: u32, layer_b: u32, mask_b: u32) -> bool {
+        layer_a & mask_b != 0 && layer_b & mask_a != 0
+    }
+}
+
+
+/// How a collider reacts to being pushed by other colliders
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum BodyKind {
+    /// Pushed around, proportionally to its inverse mass
+    #[default]
+    Dynamic,
+    /// Moved only by its own systems (input, AI), never by collisions
+    Kinematic,
+    /// Never moves at all, not even out of static obstacles
+    Static,
+}
+
+
+/// Optional companion of `Collider`. Colliders without it are dynamic with a mass of 1
+#[derive(Component, Clone, Copy)]
+pub struct Body {
+    pub mass: f32,
+    pub kind: BodyKind,
+}
+
+impl Default for Body {
+    fn default() -> Self {
+        Self { mass: 1.0, kind: BodyKind::Dynamic }
+    }
+}
+
+impl Body {
+    /// Share of a push this body takes, 0 means it can't be pushed
+    pub fn inverse_mass(&self) -> f32 {
+        match self.kind {
+            BodyKind::Dynamic if self.mass > 0.0 => 1.0 / self.mass,
+            _ => 0.0,
+        }
+    }
+}
+
+
+/// Collider that only reports overlaps through `CollisionStarted`/`CollisionEnded`,
+/// without pushing anything apart. It can be a child of the entity it belongs to,
+/// its position is then taken from `GlobalTransform`.
+#[derive(Component)]
+pub struct Sensor;
+
+
+/// Axis-aligned box that never moves, dynamic colliders get pushed out of it
+#[derive(Component)]
+pub struct StaticCollider {
+    pub half_size: Vec2,
 }
 
 
@@ -63,7 +190,9 @@ pub enum AnimationState {
     Walk,
     Attack01,
     Attack02,
+    Attack03,
     Hurt,
+    Death,
 
     // 4 directions
     IdleUp,
@@ -87,6 +216,13 @@ pub enum AnimationState {
     AttackRight,
 }
 
+impl AnimationState {
+    /// Whether the animation starts over after its last frame, instead of holding it
+    pub fn loops(self) -> bool {
+        self != AnimationState::Death
+    }
+}
+
 
 #[derive(Component)]
 pub struct Player;
@@ -96,8 +232,9 @@ pub struct Player;
 pub struct AnimationTimer(pub Timer);
 
 
-#[derive(Component)]
-struct PlayerMinion(MinionType);
+/// Type of a minnion fighting for the player
+#[derive(Component, Clone, Copy)]
+pub struct PlayerMinion(pub MinionType);
 
 #[derive(Component)]
 pub struct Target {
@@ -110,16 +247,280 @@ pub struct MoveTo {
 }
 
 #[derive(Component)]
+#[require(StatusEffects)]
 pub struct Stats{
     pub hp: i32,
     pub max_hp: i32,
     pub  attack: i32
 }
 
+/// Kind of damage, resisted separately by `Defense`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DamageType {
+    #[default]
+    Physical,
+    Fire,
+    Poison,
+}
+
+/// Damage reduction of an entity
+#[derive(Component, Default)]
+pub struct Defense {
+    /// Reduces physical damage, 100 armor halves it
+    pub armor: i32,
+    /// Fraction of damage ignored per type, 1.0 means immune, negative values are weaknesses
+    pub resistances: HashMap<DamageType, f32>,
+}
+
+/// Kind of lasting effect
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StatusKind {
+    /// Damage over time, resisted as `DamageType::Poison`
+    Poison,
+    /// Damage over time, resisted as `DamageType::Fire`
+    Burn,
+    /// Lowers movement and attack speed
+    Slow,
+    /// Can't move or attack
+    Stun,
+}
+
+impl StatusKind {
+    /// Type of the damage ticks, `None` for effects that don't deal damage
+    pub fn damage_type(self) -> Option<DamageType> {
+        match self {
+            StatusKind::Poison => Some(DamageType::Poison),
+            StatusKind::Burn => Some(DamageType::Fire),
+            StatusKind::Slow | StatusKind::Stun => None,
+        }
+    }
+}
+
+/// Lasting effect applied by an attack or an item
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    /// Seconds, refreshed when the effect is applied again
+    pub duration: f32,
+    /// Damage per second per stack for poison and burn, fraction of speed taken per stack for slow
+    pub strength: f32,
+    pub max_stacks: u32,
+}
+
+/// Running `StatusEffect`, applied by `source`
+#[derive(Debug, Clone)]
+pub struct ActiveStatus {
+    pub effect: StatusEffect,
+    pub source: Entity,
+    pub stacks: u32,
+    pub remaining: f32,
+    /// Time since the last damage tick
+    since_tick: f32,
+}
+
+/// Damage an effect deals when `StatusEffects::tick` crosses a full second
+#[derive(Debug, PartialEq)]
+pub struct StatusTick {
+    pub source: Entity,
+    pub damage: i32,
+    pub damage_type: DamageType,
+}
+
+/// Timed effects currently on an entity. Every entity with `Stats` has one
+#[derive(Component, Default)]
+pub struct StatusEffects {
+    pub active: Vec<ActiveStatus>,
+}
+
+impl StatusEffects {
+    /// Adds an effect. Applying a kind that is already running adds a stack
+    /// (up to `max_stacks`) and refreshes its duration.
+    pub fn apply(&mut self, effect: StatusEffect, source: Entity) {
+        if let Some(active) = self.active.iter_mut().find(|a| a.effect.kind == effect.kind) {
+            active.stacks = (active.stacks + 1).min(effect.max_stacks.max(1));
+            active.remaining = active.remaining.max(effect.duration);
+            active.effect = effect;
+            active.source = source;
+            return;
+        }
+
+        self.active.push(ActiveStatus { effect, source, stacks: 1, remaining: effect.duration, since_tick: 0.0 });
+    }
+
+    pub fn is_stunned(&self) -> bool {
+        self.active.iter().any(|a| a.effect.kind == StatusKind::Stun)
+    }
+
+    /// Factor for movement speed, 0 while stunned
+    pub fn speed_multiplier(&self) -> f32 {
+        if self.is_stunned() {
+            return 0.0;
+        }
+
+        self.active
+            .iter()
+            .filter(|a| a.effect.kind == StatusKind::Slow)
+            .map(|a| (1.0 - a.effect.strength.clamp(0.0, 1.0)).powi(a.stacks as i32))
+            .product()
+    }
+
+    /// Factor for how fast attack cooldowns run, slows make them run longer and so space out
+    /// swings, shots and heals. A stun stops them.
+    pub fn attack_speed_multiplier(&self) -> f32 {
+        self.speed_multiplier()
+    }
+
+    /// Advances every effect by `dt` seconds, drops the expired ones
+    /// and returns the damage ticks that came due
+    pub fn tick(&mut self, dt: f32) -> Vec<StatusTick> {
+        let mut ticks = Vec::new();
+
+        for active in self.active.iter_mut() {
+            // Damage is dealt for the time the effect was still running
+            let elapsed = dt.min(active.remaining);
+            active.remaining -= dt;
+
+            let Some(damage_type) = active.effect.kind.damage_type() else {
+                continue;
+            };
+
+            active.since_tick += elapsed;
+            while active.since_tick >= 1.0 {
+                active.since_tick -= 1.0;
+                ticks.push(StatusTick {
+                    source: active.source,
+                    damage: (active.effect.strength * active.stacks as f32).round() as i32,
+                    damage_type,
+                });
+            }
+        }
+
+        self.active.retain(|a| a.remaining > 0.0);
+        ticks
+    }
+}
+
+/// Puts a status effect on `target`, sent by attacks that carry effects and by items
+#[derive(Event)]
+pub struct ApplyStatus {
+    pub target: Entity,
+    pub source: Entity,
+    pub effect: StatusEffect,
+}
+
+/// Velocity change this entity's hits push the target away with
+#[derive(Component, Clone, Copy)]
+pub struct Knockback {
+    pub impulse: f32,
+}
+
+/// Chance for this entity's attacks to deal extra damage
+#[derive(Component, Clone, Copy)]
+pub struct Critical {
+    /// Between 0 and 1
+    pub chance: f32,
+    pub multiplier: f32,
+}
+
+
+/// Entity that ran out of HP, plays its death animation until the timer runs out and the corpse is removed
+#[derive(Component)]
+pub struct Dying {
+    pub timer: Timer,
+}
+
+/// Sent once when an entity's HP drops to 0. `killer` is whoever dealt the last blow, if anyone
+#[derive(Event)]
+pub struct Died {
+    pub entity: Entity,
+    pub killer: Option<Entity>,
+}
+
+/// Top-level game state, the game is over once the player dies
+#[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum GameState {
+    #[default]
+    Playing,
+    GameOver,
+}
+
 
 #[derive(Event)]
 pub struct AttackEvent {
     pub attacker: Entity,
     pub target: Entity,
     pub damage: i32,
-}
\ No newline at end of file
+    pub damage_type: DamageType,
+    /// Applied to the target if the hit lands
+    pub effects: Vec<StatusEffect>,
+}
+
+/// Sent after an attack went through the damage pipeline, with the damage actually taken
+#[derive(Event)]
+pub struct DamageDealt {
+    pub attacker: Entity,
+    pub target: Entity,
+    pub amount: i32,
+    pub damage_type: DamageType,
+    pub critical: bool,
+}
+
+
+/// Sent when two colliders start overlapping
+#[derive(Event)]
+pub struct CollisionStarted {
+    pub a: Entity,
+    pub b: Entity,
+}
+
+/// Sent when two colliders stop overlapping, or one of them is despawned
+#[derive(Event)]
+pub struct CollisionEnded {
+    pub a: Entity,
+    pub b: Entity,
+}
+
+impl CollisionStarted {
+    /// Returns the other entity of the pair if `entity` is part of it
+    pub fn other(&self, entity: Entity) -> Option<Entity> {
+        other_of(self.a, self.b, entity)
+    }
+}
+
+impl CollisionEnded {
+    /// Returns the other entity of the pair if `entity` is part of it
+    pub fn other(&self, entity: Entity) -> Option<Entity> {
+        other_of(self.a, self.b, entity)
+    }
+}
+
+fn other_of(a: Entity, b: Entity, entity: Entity) -> Option<Entity> {
+    if a == entity {
+        Some(b)
+    } else if b == entity {
+        Some(a)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hostility_is_symmetric_and_editable() {
+        let mut table = FactionTable::default();
+        assert!(table.is_hostile(Faction::Orcs, Faction::Player));
+        assert!(table.is_hostile(Faction::Player, Faction::Orcs));
+        assert!(!table.is_hostile(Faction::Orcs, Faction::Orcs));
+        assert!(!table.is_hostile(Faction::Neutral, Faction::Wildlife));
+
+        // Orc infighting
+        table.set_hostile(Faction::Orcs, Faction::Orcs, true);
+        assert!(table.is_hostile(Faction::Orcs, Faction::Orcs));
+
+        table.set_hostile(Faction::Orcs, Faction::Player, false);
+        assert!(!table.is_hostile(Faction::Player, Faction::Orcs));
+    }
+}
@@ -0,0 +1,323 @@
+use bevy::{platform::collections::HashMap, prelude::*};
+use crate::core::common::{Animation, AnimationState, AttackEvent, DamageType, Faction, FactionTable, Stats, StatusEffect, StatusEffects};
+
+/// Plugin resolving melee attacks: hitboxes on attack animation frames against hurtboxes
+pub struct HitboxPlugin;
+
+impl Plugin for HitboxPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, resolve_hitboxes);
+    }
+}
+
+/// Shape of an attack relative to the attacker, for an attacker facing +X
+#[derive(Clone, Copy)]
+pub enum HitShape {
+    /// Slice of a circle centered on the attacker, `half_angle` in radians on each side of the facing
+    Arc { radius: f32, half_angle: f32 },
+    /// Box whose center sits at `offset` (X points forward)
+    Rect { offset: Vec2, half_size: Vec2 },
+}
+
+/// Hitbox active while the animation shows frames `first..=last` (atlas indices)
+#[derive(Clone, Copy)]
+pub struct HitboxFrames {
+    pub first: usize,
+    pub last: usize,
+    pub shape: HitShape,
+}
+
+/// Melee hitboxes of an entity, keyed by the animation state they belong to. They only hit
+/// hurtboxes of factions hostile to the attacker's `Faction`, every hit sends an `AttackEvent`
+/// with the attacker's `Stats::attack` as damage.
+#[derive(Component)]
+#[require(SwingHits)]
+pub struct Hitboxes {
+    pub frames: HashMap<AnimationState, Vec<HitboxFrames>>,
+    pub damage_type: DamageType,
+    /// Status effects put on every target hit
+    pub on_hit: Vec<StatusEffect>,
+}
+
+impl Hitboxes {
+    /// Same physical damage hitboxes for every given animation state
+    pub fn for_states(states: &[AnimationState], frames: &[HitboxFrames]) -> Self {
+        Self {
+            frames: states.iter().map(|state| (*state, frames.to_vec())).collect(),
+            damage_type: DamageType::Physical,
+            on_hit: Vec::new(),
+        }
+    }
+}
+
+/// Area where an entity can be hit, an axis-aligned box around its `Transform`
+#[derive(Component)]
+pub struct Hurtbox {
+    pub offset: Vec2,
+    pub half_size: Vec2,
+}
+
+/// Targets already hit during the current swing, so each one is hit at most once
+#[derive(Component, Default)]
+pub struct SwingHits {
+    state: Option<AnimationState>,
+    frame: usize,
+    hit: Vec<Entity>,
+}
+
+impl SwingHits {
+    /// Moves to the given animation frame. A new swing starts when the animation state
+    /// changes or the frame goes back (the attack animation looped).
+    fn advance(&mut self, state: AnimationState, frame: usize) {
+        if self.state != Some(state) || frame < self.frame {
+            self.hit.clear();
+        }
+        self.state = Some(state);
+        self.frame = frame;
+    }
+}
+
+/// Whether `animation` is still playing the swing `state`, short of its last frame
+pub fn swinging(animation: &Animation, sprite: &Sprite, state: AnimationState) -> bool {
+    if animation.state != state {
+        return false;
+    }
+    // The texture switches later in the frame, the swing has only just started
+    if animation.last_state != Some(state) {
+        return true;
+    }
+
+    let last = animation.set.animations.get(&state).map(|(_, _, indices)| indices.last);
+    let frame = sprite.texture_atlas.as_ref().map(|atlas| atlas.index);
+    matches!((frame, last), (Some(frame), Some(last)) if frame < last)
+}
+
+/// Starts a new swing once the previous one played out and `cooldown` is up, restarting the cooldown
+pub fn start_swing(cooldown: &mut Timer, swinging: bool) -> bool {
+    if swinging || !cooldown.finished() {
+        return false;
+    }
+    cooldown.reset();
+    true
+}
+
+/// Direction the attacker faces. Directional attack states win, otherwise the
+/// sprite's horizontal flip is used (side-view sprites face right by default).
+pub fn facing(state: AnimationState, transform: &Transform) -> Vec2 {
+    match state {
+        AnimationState::AttackUp => Vec2::Y,
+        AnimationState::AttackDown => Vec2::NEG_Y,
+        AnimationState::AttackLeft => Vec2::NEG_X,
+        AnimationState::AttackRight => Vec2::X,
+        _ if transform.scale.x < 0.0 => Vec2::NEG_X,
+        _ => Vec2::X,
+    }
+}
+
+/// Tests a hit shape of an attacker at `origin` facing `facing` (unit length) against a box
+pub fn shape_hits_rect(shape: HitShape, origin: Vec2, facing: Vec2, rect: Rect) -> bool {
+    match shape {
+        HitShape::Arc { radius, half_angle } => arc_hits_rect(origin, facing, radius, half_angle, rect),
+        HitShape::Rect { offset, half_size } => {
+            // Rotate the box into world space, take its bounds
+            let center = origin + facing.rotate(offset);
+            let extent = facing.rotate(half_size).abs().max(facing.rotate(half_size.with_x(-half_size.x)).abs());
+            !Rect::from_center_half_size(center, extent).intersect(rect).is_empty()
+        }
+    }
+}
+
+fn in_arc(point: Vec2, origin: Vec2, facing: Vec2, radius: f32, half_angle: f32) -> bool {
+    let delta = point - origin;
+    delta.length_squared() <= radius * radius
+        && (delta == Vec2::ZERO || facing.angle_to(delta).abs() <= half_angle)
+}
+
+fn arc_hits_rect(origin: Vec2, facing: Vec2, radius: f32, half_angle: f32, rect: Rect) -> bool {
+    // Attacker standing inside the box
+    if rect.contains(origin) {
+        return true;
+    }
+
+    // Closest point of the box, or one of its corners, inside the slice
+    let closest = origin.clamp(rect.min, rect.max);
+    let corners = [rect.min, rect.max, Vec2::new(rect.min.x, rect.max.y), Vec2::new(rect.max.x, rect.min.y)];
+    if std::iter::once(closest).chain(corners).any(|p| in_arc(p, origin, facing, radius, half_angle)) {
+        return true;
+    }
+
+    // Box crossing one of the straight edges of the slice
+    [-half_angle, half_angle]
+        .into_iter()
+        .map(|angle| origin + Vec2::from_angle(angle).rotate(facing) * radius)
+        .any(|end| segment_hits_rect(origin, end, rect))
+}
+
+/// Slab test of the segment `a..b` against a box
+fn segment_hits_rect(a: Vec2, b: Vec2, rect: Rect) -> bool {
+    let dir = b - a;
+    let (mut t_min, mut t_max) = (0.0_f32, 1.0_f32);
+
+    for axis in 0..2 {
+        if dir[axis].abs() < f32::EPSILON {
+            // Parallel to this slab, has to start inside of it
+            if a[axis] < rect.min[axis] || a[axis] > rect.max[axis] {
+                return false;
+            }
+            continue;
+        }
+
+        let t1 = (rect.min[axis] - a[axis]) / dir[axis];
+        let t2 = (rect.max[axis] - a[axis]) / dir[axis];
+        t_min = t_min.max(t1.min(t2));
+        t_max = t_max.min(t1.max(t2));
+        if t_min > t_max {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// Everything `resolve_hitboxes` reads from an attacker
+type Attacker<'a> = (
+    Entity,
+    &'a Transform,
+    &'a Animation,
+    &'a Sprite,
+    &'a Hitboxes,
+    &'a Stats,
+    &'a Faction,
+    &'a StatusEffects,
+    &'a mut SwingHits,
+);
+
+/// Tests the hitboxes of the current animation frame against every hurtbox and sends
+/// an `AttackEvent` for every target hit for the first time in this swing.
+/// Stunned attackers don't hit anything.
+fn resolve_hitboxes(
+    mut attackers: Query<Attacker>,
+    hurtboxes: Query<(Entity, &Transform, &Hurtbox, &Faction)>,
+    factions: Res<FactionTable>,
+    mut attack_events: EventWriter<AttackEvent>,
+) {
+    for (attacker, transform, animation, sprite, hitboxes, stats, faction, statuses, mut swing) in attackers.iter_mut() {
+        let Some(frame) = sprite.texture_atlas.as_ref().map(|atlas| atlas.index) else {
+            continue;
+        };
+        swing.advance(animation.state, frame);
+
+        if statuses.is_stunned() {
+            continue;
+        }
+
+        let Some(frames) = hitboxes.frames.get(&animation.state) else {
+            continue;
+        };
+
+        let origin = transform.translation.truncate();
+        let facing = facing(animation.state, transform);
+
+        for active in frames.iter().filter(|f| (f.first..=f.last).contains(&frame)) {
+            for (target, target_tf, hurtbox, target_faction) in hurtboxes.iter() {
+                if target == attacker || !factions.is_hostile(*faction, *target_faction) || swing.hit.contains(&target) {
+                    continue;
+                }
+
+                let rect = Rect::from_center_half_size(target_tf.translation.truncate() + hurtbox.offset, hurtbox.half_size);
+                if shape_hits_rect(active.shape, origin, facing, rect) {
+                    swing.hit.push(target);
+                    attack_events.write(AttackEvent {
+                        attacker,
+                        target,
+                        damage: stats.attack,
+                        damage_type: hitboxes.damage_type,
+                        effects: hitboxes.on_hit.clone(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{f32::consts::FRAC_PI_4, time::Duration};
+
+    /// 20x20 box around the given point
+    fn target(center: Vec2) -> Rect {
+        Rect::from_center_half_size(center, Vec2::splat(10.0))
+    }
+
+    #[test]
+    fn arc_hits_box_in_front_only() {
+        let arc = HitShape::Arc { radius: 100.0, half_angle: FRAC_PI_4 };
+
+        assert!(shape_hits_rect(arc, Vec2::ZERO, Vec2::X, target(Vec2::new(80.0, 0.0))));
+        assert!(!shape_hits_rect(arc, Vec2::ZERO, Vec2::X, target(Vec2::new(-80.0, 0.0))));
+        assert!(!shape_hits_rect(arc, Vec2::ZERO, Vec2::X, target(Vec2::new(150.0, 0.0))));
+        assert!(shape_hits_rect(arc, Vec2::ZERO, Vec2::Y, target(Vec2::new(0.0, 80.0))));
+    }
+
+    #[test]
+    fn arc_hits_big_box_across_its_edge() {
+        let arc = HitShape::Arc { radius: 100.0, half_angle: FRAC_PI_4 };
+
+        // Wide box above the slice: no corner or closest point inside it, but the upper edge crosses it
+        let wall = Rect::new(-500.0, 40.0, 500.0, 60.0);
+        assert!(shape_hits_rect(arc, Vec2::ZERO, Vec2::X, wall));
+        assert!(!shape_hits_rect(arc, Vec2::ZERO, Vec2::NEG_Y, wall));
+    }
+
+    #[test]
+    fn rect_follows_facing() {
+        let slash = HitShape::Rect { offset: Vec2::new(50.0, 0.0), half_size: Vec2::new(30.0, 10.0) };
+
+        assert!(shape_hits_rect(slash, Vec2::ZERO, Vec2::X, target(Vec2::new(70.0, 0.0))));
+        assert!(!shape_hits_rect(slash, Vec2::ZERO, Vec2::NEG_X, target(Vec2::new(70.0, 0.0))));
+        // Facing down the box is tall and thin
+        assert!(shape_hits_rect(slash, Vec2::ZERO, Vec2::NEG_Y, target(Vec2::new(0.0, -75.0))));
+        assert!(!shape_hits_rect(slash, Vec2::ZERO, Vec2::NEG_Y, target(Vec2::new(25.0, -50.0))));
+    }
+
+    #[test]
+    fn swing_restarts_when_animation_loops() {
+        let mut swing = SwingHits::default();
+
+        swing.advance(AnimationState::Attack01, 2);
+        swing.hit.push(Entity::from_raw(1));
+        swing.advance(AnimationState::Attack01, 3);
+        assert_eq!(swing.hit.len(), 1);
+
+        swing.advance(AnimationState::Attack01, 0);
+        assert!(swing.hit.is_empty());
+    }
+
+    /// Swings started in `secs` seconds by an attacker whose cooldown runs at `multiplier`,
+    /// with 0.6s swings and a 0.6s cooldown that's up from the start
+    fn swings_in(secs: u32, multiplier: f32) -> u32 {
+        let step = Duration::from_millis(100);
+        let mut cooldown = Timer::new(Duration::from_millis(600), TimerMode::Once);
+        cooldown.tick(cooldown.duration());
+        let (mut swings, mut since_swing) = (0, u32::MAX);
+
+        for _ in 0..secs * 10 {
+            if start_swing(&mut cooldown, since_swing < 6) {
+                swings += 1;
+                since_swing = 0;
+            }
+            cooldown.tick(step.mul_f32(multiplier));
+            since_swing = since_swing.saturating_add(1);
+        }
+        swings
+    }
+
+    #[test]
+    fn slowed_attackers_swing_less_often() {
+        assert_eq!(swings_in(6, 1.0), 10);
+        assert_eq!(swings_in(6, 0.5), 5);
+        // Stunned ones finish the swing they started, the cooldown stands still after that
+        assert_eq!(swings_in(6, 0.0), 1);
+    }
+}
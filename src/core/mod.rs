@@ -1,4 +1,11 @@
 pub mod common;
 pub mod combat;
 pub mod collision;
-pub mod animation;
\ No newline at end of file
+pub mod animation;
+pub mod hitbox;
+pub mod movement;
+pub mod status;
+pub mod projectile;
+pub mod targeting;
+pub mod navigation;
+pub mod steering;
\ No newline at end of file
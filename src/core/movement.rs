@@ -0,0 +1,72 @@
+use bevy::prelude::*;
+use crate::core::collision::rebuild_spatial_grid;
+use crate::core::common::Velocity;
+use crate::core::steering::Steering;
+
+/// Plugin moving entities by their `Velocity`
+pub struct MovementPlugin;
+
+/// How fast velocity fades out, per second
+const DAMPING: f32 = 8.0;
+
+/// Below this speed an entity is considered stopped
+const STOP_SPEED: f32 = 1.0;
+
+impl Plugin for MovementPlugin {
+    fn build(&self, app: &mut App) {
+        // Move before the collision pass, so it can push entities back out of each other
+        app.add_systems(Update, apply_velocity.before(rebuild_spatial_grid));
+    }
+}
+
+/// Moves entities by their `Velocity` and lets it fade out, so impulses like knockback
+/// end up as a short shove. Steered units aren't damped, their `Steering` slows them down.
+pub fn apply_velocity(
+    time: Res<Time>,
+    mut query: Query<(&mut Transform, &mut Velocity, Has<Steering>)>,
+) {
+    let dt = time.delta_secs();
+
+    for (mut transform, mut velocity, steered) in query.iter_mut() {
+        if velocity.0 == Vec3::ZERO {
+            continue;
+        }
+
+        transform.translation += velocity.0 * dt;
+        if !steered {
+            velocity.0 = damp(velocity.0, dt);
+        }
+    }
+}
+
+/// Velocity left after `dt` seconds of damping
+pub fn damp(velocity: Vec3, dt: f32) -> Vec3 {
+    let damped = velocity * (-DAMPING * dt).exp();
+
+    if damped.length_squared() < STOP_SPEED * STOP_SPEED {
+        Vec3::ZERO
+    } else {
+        damped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn impulse_fades_out_after_a_short_distance() {
+        let mut velocity = Vec3::new(800.0, 0.0, 0.0);
+        let mut travelled = 0.0;
+
+        // Two seconds at 60 fps
+        for _ in 0..120 {
+            travelled += velocity.x / 60.0;
+            velocity = damp(velocity, 1.0 / 60.0);
+        }
+
+        assert_eq!(velocity, Vec3::ZERO);
+        // Roughly impulse / DAMPING
+        assert!((travelled - 800.0 / DAMPING).abs() < 10.0, "{travelled}");
+    }
+}
@@ -0,0 +1,519 @@
+use bevy::{
+    platform::collections::{HashMap, HashSet},
+    prelude::*,
+    tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task},
+};
+use std::{cmp::Reverse, collections::BinaryHeap, sync::Arc};
+
+/// Goal has to move this far from where the current path leads before it's planned again
+const REPLAN_DISTANCE: f32 = 60.0;
+/// Cost of a straight and a diagonal step, integers keep the open set ordering simple
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+/// How many rings around a blocked goal are searched for a free cell
+const GOAL_SEARCH_RINGS: i32 = 3;
+
+/// Plugin planning paths off the main thread and handing them to `Navigator`s
+pub struct NavigationPlugin;
+
+impl Plugin for NavigationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FlowFields>()
+            .add_systems(Update, ((plan_paths, collect_paths).chain(), update_flow_fields));
+    }
+}
+
+/// Walkable cells of the map, built by `world::map` from the map colliders.
+/// Cheap to clone, path queries get their own copy on the task pool.
+#[derive(Resource, Clone)]
+pub struct NavGrid {
+    origin: Vec2,
+    cell_size: f32,
+    width: i32,
+    height: i32,
+    blocked: Arc<Vec<bool>>,
+}
+
+impl NavGrid {
+    /// Grid covering `bounds`. Cells closer than `clearance` to an obstacle are blocked,
+    /// so agents following the path don't scrape along walls.
+    pub fn from_obstacles(obstacles: &[Rect], bounds: Rect, cell_size: f32, clearance: f32) -> Self {
+        let width = (bounds.width() / cell_size).ceil().max(1.0) as i32;
+        let height = (bounds.height() / cell_size).ceil().max(1.0) as i32;
+        let inflated: Vec<Rect> = obstacles.iter().map(|rect| rect.inflate(clearance)).collect();
+
+        let mut blocked = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let min = bounds.min + Vec2::new(x as f32, y as f32) * cell_size;
+                let cell = Rect::from_corners(min, min + Vec2::splat(cell_size));
+                blocked.push(inflated.iter().any(|rect| !rect.intersect(cell).is_empty()));
+            }
+        }
+
+        Self { origin: bounds.min, cell_size, width, height, blocked: Arc::new(blocked) }
+    }
+
+    pub fn cell_of(&self, point: Vec2) -> IVec2 {
+        ((point - self.origin) / self.cell_size).floor().as_ivec2()
+    }
+
+    pub fn center_of(&self, cell: IVec2) -> Vec2 {
+        self.origin + (cell.as_vec2() + Vec2::splat(0.5)) * self.cell_size
+    }
+
+    /// Cells outside the grid are walkable, the map walls keep agents inside anyway
+    pub fn is_walkable(&self, cell: IVec2) -> bool {
+        if cell.x < 0 || cell.y < 0 || cell.x >= self.width || cell.y >= self.height {
+            return true;
+        }
+        !self.blocked[(cell.y * self.width + cell.x) as usize]
+    }
+
+    fn index(&self, cell: IVec2) -> Option<usize> {
+        (cell.x >= 0 && cell.y >= 0 && cell.x < self.width && cell.y < self.height)
+            .then_some((cell.y * self.width + cell.x) as usize)
+    }
+
+    /// Closest walkable cell to `cell`, looking a few rings out
+    fn nearest_walkable(&self, cell: IVec2) -> Option<IVec2> {
+        (0..=GOAL_SEARCH_RINGS).find_map(|ring| {
+            (-ring..=ring)
+                .flat_map(|dx| (-ring..=ring).map(move |dy| cell + IVec2::new(dx, dy)))
+                .filter(|candidate| (*candidate - cell).abs().max_element() == ring)
+                .find(|candidate| self.index(*candidate).is_some() && self.is_walkable(*candidate))
+        })
+    }
+
+    fn cell_at(&self, index: usize) -> IVec2 {
+        IVec2::new(index as i32 % self.width, index as i32 / self.width)
+    }
+
+    /// Cells reachable in one step from `cell` with the step cost. Diagonals
+    /// need both cells beside them free, so corners are never cut.
+    fn neighbours(&self, cell: IVec2) -> impl Iterator<Item = (usize, IVec2, u32)> + '_ {
+        (-1..=1)
+            .flat_map(|dx| (-1..=1).map(move |dy| IVec2::new(dx, dy)))
+            .filter(|step| *step != IVec2::ZERO)
+            .filter_map(move |step| {
+                let next = cell + step;
+                let next_index = self.index(next)?;
+                let diagonal = step.x != 0 && step.y != 0;
+                let blocked = !self.is_walkable(next)
+                    || (diagonal && (!self.is_walkable(cell + step.with_y(0)) || !self.is_walkable(cell + step.with_x(0))));
+
+                (!blocked).then_some((next_index, next, if diagonal { DIAGONAL_COST } else { STRAIGHT_COST }))
+            })
+    }
+
+    /// Whether the straight line `a..b` only crosses walkable cells
+    fn line_is_clear(&self, a: Vec2, b: Vec2) -> bool {
+        let steps = (a.distance(b) / (self.cell_size * 0.25)).ceil().max(1.0) as i32;
+        (0..=steps).all(|step| self.is_walkable(self.cell_of(a.lerp(b, step as f32 / steps as f32))))
+    }
+
+    /// A* from `start` to `goal` on the 8-connected grid, without cutting corners.
+    /// Returns the waypoints after `start`, ending exactly at `goal`, or `None` without a way there.
+    pub fn find_path(&self, start: Vec2, goal: Vec2) -> Option<Vec<Vec2>> {
+        let start_cell = self.cell_of(start);
+        let (Some(start_index), Some(_)) = (self.index(start_cell), self.index(self.cell_of(goal))) else {
+            // Start or goal off the grid, nothing to walk around out there
+            return Some(vec![goal]);
+        };
+        let goal_cell = self.nearest_walkable(self.cell_of(goal))?;
+        let goal_index = self.index(goal_cell)?;
+
+        let heuristic = |cell: IVec2| {
+            let d = (cell - goal_cell).abs();
+            STRAIGHT_COST * d.max_element() as u32 + (DIAGONAL_COST - STRAIGHT_COST) * d.min_element() as u32
+        };
+
+        let mut cost = vec![u32::MAX; self.blocked.len()];
+        let mut came_from = vec![usize::MAX; self.blocked.len()];
+        let mut open = BinaryHeap::new();
+        cost[start_index] = 0;
+        open.push(Reverse((heuristic(start_cell), start_index)));
+
+        while let Some(Reverse((_, index))) = open.pop() {
+            if index == goal_index {
+                break;
+            }
+
+            for (next_index, next, step_cost) in self.neighbours(self.cell_at(index)) {
+                let next_cost = cost[index] + step_cost;
+                if next_cost < cost[next_index] {
+                    cost[next_index] = next_cost;
+                    came_from[next_index] = index;
+                    open.push(Reverse((next_cost + heuristic(next), next_index)));
+                }
+            }
+        }
+
+        if cost[goal_index] == u32::MAX {
+            return None;
+        }
+
+        let mut cells = Vec::new();
+        let mut index = goal_index;
+        while index != start_index {
+            cells.push(self.center_of(self.cell_at(index)));
+            index = came_from[index];
+        }
+        cells.reverse();
+
+        // End exactly at the goal, or in the middle of the free cell it got moved to
+        let end = if goal_cell == self.cell_of(goal) { goal } else { self.center_of(goal_cell) };
+        cells.pop();
+        cells.push(end);
+
+        Some(self.smooth(start, cells))
+    }
+
+    /// Flow field leading every reachable cell to the cell of `goal`
+    pub fn flow_field(&self, goal: Vec2) -> FlowField {
+        let goal_cell = self.cell_of(goal);
+        let mut cost = vec![u32::MAX; self.blocked.len()];
+        let mut open = BinaryHeap::new();
+        if let Some(goal_index) = self.index(goal_cell) {
+            cost[goal_index] = 0;
+            open.push(Reverse((0, goal_index)));
+        }
+
+        // Dijkstra out from the goal, moves are symmetric so this is the cost to get there
+        while let Some(Reverse((cell_cost, index))) = open.pop() {
+            if cell_cost > cost[index] {
+                continue;
+            }
+            for (next_index, _, step_cost) in self.neighbours(self.cell_at(index)) {
+                let next_cost = cell_cost + step_cost;
+                if next_cost < cost[next_index] {
+                    cost[next_index] = next_cost;
+                    open.push(Reverse((next_cost, next_index)));
+                }
+            }
+        }
+
+        // Every cell points at its cheapest neighbour
+        let directions = (0..cost.len())
+            .map(|index| {
+                let cell = self.cell_at(index);
+                if cost[index] == u32::MAX || cell == goal_cell {
+                    return None;
+                }
+                self.neighbours(cell)
+                    .min_by_key(|(next_index, _, _)| cost[*next_index])
+                    .filter(|(next_index, _, _)| cost[*next_index] < cost[index])
+                    .map(|(_, next, _)| (next - cell).as_vec2().normalize())
+            })
+            .collect();
+
+        FlowField { grid: self.clone(), goal_cell, directions }
+    }
+
+    /// Drops waypoints that can be skipped by walking straight to a later one
+    fn smooth(&self, start: Vec2, waypoints: Vec<Vec2>) -> Vec<Vec2> {
+        let mut smoothed = Vec::new();
+        let mut from = start;
+        let mut i = 0;
+        while i < waypoints.len() {
+            let mut furthest = i;
+            while furthest + 1 < waypoints.len() && self.line_is_clear(from, waypoints[furthest + 1]) {
+                furthest += 1;
+            }
+            from = waypoints[furthest];
+            smoothed.push(from);
+            i = furthest + 1;
+        }
+        smoothed
+    }
+}
+
+/// Direction to walk in from every cell of a `NavGrid` to reach one goal cell
+pub struct FlowField {
+    grid: NavGrid,
+    goal_cell: IVec2,
+    /// `None` for blocked and unreachable cells, and for the goal cell itself
+    directions: Vec<Option<Vec2>>,
+}
+
+impl FlowField {
+    /// Direction to walk in from `position` to reach `goal`, which has to lie in the goal cell.
+    /// `None` where the field doesn't lead anywhere (off the grid, inside a wall).
+    pub fn direction(&self, position: Vec2, goal: Vec2) -> Option<Vec2> {
+        let cell = self.grid.cell_of(position);
+        if cell == self.goal_cell {
+            return Some((goal - position).normalize_or_zero());
+        }
+        self.directions[self.grid.index(cell)?]
+    }
+}
+
+/// Move order shared by a group, the entity walks along the flow field of `FlowFields`
+/// towards this point instead of planning its own path
+#[derive(Component, Clone, Copy)]
+pub struct FlowFieldGoal(pub Vec2);
+
+enum FlowFieldSlot {
+    Planning(Task<FlowField>),
+    Ready(FlowField),
+}
+
+/// One flow field per goal cell any `FlowFieldGoal` points into, computed on the task pool
+/// and dropped once nobody walks there anymore
+#[derive(Resource, Default)]
+pub struct FlowFields {
+    fields: HashMap<IVec2, FlowFieldSlot>,
+}
+
+impl FlowFields {
+    /// Direction from `position` towards `goal`, `None` while the field is being computed
+    /// or doesn't lead anywhere from there
+    pub fn direction(&self, grid: &NavGrid, goal: Vec2, position: Vec2) -> Option<Vec2> {
+        match self.fields.get(&grid.cell_of(goal))? {
+            FlowFieldSlot::Ready(field) => field.direction(position, goal),
+            FlowFieldSlot::Planning(_) => None,
+        }
+    }
+}
+
+/// Walks an entity to a goal around obstacles. Movement code sets the goal and asks
+/// for a direction, paths are planned in the background and re-planned once the goal
+/// moved more than `REPLAN_DISTANCE`. Until a path is ready it heads straight for the goal.
+#[derive(Component, Default)]
+pub struct Navigator {
+    goal: Option<Vec2>,
+    /// Goal the current path (or the one being planned) leads to
+    planned_for: Option<Vec2>,
+    path: Vec<Vec2>,
+    next: usize,
+    task: Option<Task<Option<Vec<Vec2>>>>,
+}
+
+impl Navigator {
+    pub fn set_goal(&mut self, goal: Option<Vec2>) {
+        self.goal = goal;
+        // A path still being planned for a goal that's gone or moved too far is dropped, which cancels it
+        if goal.is_none() || self.needs_plan() {
+            self.task = None;
+        }
+        if goal.is_none() {
+            self.planned_for = None;
+            self.path.clear();
+        }
+    }
+
+    fn needs_plan(&self) -> bool {
+        match (self.goal, self.planned_for) {
+            (Some(_), None) => true,
+            (Some(goal), Some(planned)) => goal.distance(planned) > REPLAN_DISTANCE,
+            (None, _) => false,
+        }
+    }
+
+    /// Unit direction to walk in from `position`, zero without a goal
+    pub fn steer(&mut self, position: Vec2, arrive_radius: f32) -> Vec2 {
+        let Some(goal) = self.goal else {
+            return Vec2::ZERO;
+        };
+
+        while self.next < self.path.len() && position.distance(self.path[self.next]) <= arrive_radius {
+            self.next += 1;
+        }
+        let waypoint = self.path.get(self.next).copied().unwrap_or(goal);
+
+        (waypoint - position).normalize_or_zero()
+    }
+}
+
+/// Starts a path query for every navigator whose goal moved too far from its path
+fn plan_paths(
+    mut navigators: Query<(&Transform, &mut Navigator)>,
+    grid: Option<Res<NavGrid>>,
+) {
+    let Some(grid) = grid else {
+        return;
+    };
+    let pool = AsyncComputeTaskPool::get();
+
+    for (transform, mut navigator) in navigators.iter_mut() {
+        if navigator.task.is_some() || !navigator.needs_plan() {
+            continue;
+        }
+        let Some(goal) = navigator.goal else {
+            continue;
+        };
+
+        let grid = grid.clone();
+        let start = transform.translation.truncate();
+        navigator.planned_for = Some(goal);
+        navigator.task = Some(pool.spawn(async move { grid.find_path(start, goal) }));
+    }
+}
+
+/// Hands finished path queries to their navigators. No path means walking straight at the goal.
+fn collect_paths(mut navigators: Query<&mut Navigator>) {
+    for mut navigator in navigators.iter_mut() {
+        let Some(result) = navigator.task.as_mut().and_then(|task| block_on(future::poll_once(task))) else {
+            continue;
+        };
+
+        navigator.task = None;
+        if navigator.goal.is_none() {
+            continue;
+        }
+        navigator.path = result.unwrap_or_default();
+        navigator.next = 0;
+    }
+}
+
+/// Starts flow fields for new group goals, collects finished ones and drops unused ones
+fn update_flow_fields(
+    mut flow_fields: ResMut<FlowFields>,
+    grid: Option<Res<NavGrid>>,
+    goals: Query<&FlowFieldGoal>,
+) {
+    let Some(grid) = grid else {
+        return;
+    };
+    // Fields of an old grid lead into the walls of the new one
+    if grid.is_changed() {
+        flow_fields.fields.clear();
+    }
+
+    let wanted: HashSet<IVec2> = goals.iter().map(|goal| grid.cell_of(goal.0)).collect();
+    flow_fields.fields.retain(|cell, _| wanted.contains(cell));
+
+    let pool = AsyncComputeTaskPool::get();
+    for cell in wanted {
+        flow_fields.fields.entry(cell).or_insert_with(|| {
+            let grid = grid.clone();
+            let center = grid.center_of(cell);
+            FlowFieldSlot::Planning(pool.spawn(async move { grid.flow_field(center) }))
+        });
+    }
+
+    for slot in flow_fields.fields.values_mut() {
+        let finished = match slot {
+            FlowFieldSlot::Planning(task) => block_on(future::poll_once(task)),
+            FlowFieldSlot::Ready(_) => None,
+        };
+        if let Some(field) = finished {
+            *slot = FlowFieldSlot::Ready(field);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::tasks::TaskPool;
+
+    /// 10x10 cells of 10 units with a wall across x = 40..60, open at the top
+    fn walled_grid() -> NavGrid {
+        NavGrid::from_obstacles(
+            &[Rect::new(40.0, 0.0, 60.0, 80.0)],
+            Rect::new(0.0, 0.0, 100.0, 100.0),
+            10.0,
+            0.0,
+        )
+    }
+
+    #[test]
+    fn obstacles_block_cells() {
+        let grid = walled_grid();
+
+        assert!(!grid.is_walkable(IVec2::new(4, 3)));
+        assert!(grid.is_walkable(IVec2::new(4, 9)));
+        assert!(grid.is_walkable(IVec2::new(1, 1)));
+    }
+
+    #[test]
+    fn path_goes_around_the_wall() {
+        let grid = walled_grid();
+        let start = Vec2::new(15.0, 15.0);
+        let goal = Vec2::new(85.0, 15.0);
+
+        let path = grid.find_path(start, goal).unwrap();
+
+        assert_eq!(path.last(), Some(&goal));
+        assert!(path.iter().any(|point| point.y > 80.0));
+        // Every leg of the smoothed path is walkable
+        let mut from = start;
+        for point in path {
+            assert!(grid.line_is_clear(from, point));
+            from = point;
+        }
+    }
+
+    #[test]
+    fn open_ground_is_a_straight_line() {
+        let grid = walled_grid();
+        let goal = Vec2::new(25.0, 75.0);
+
+        assert_eq!(grid.find_path(Vec2::new(15.0, 15.0), goal), Some(vec![goal]));
+    }
+
+    #[test]
+    fn sealed_goal_has_no_path() {
+        let grid = NavGrid::from_obstacles(
+            &[Rect::new(0.0, 40.0, 100.0, 60.0)],
+            Rect::new(0.0, 0.0, 100.0, 100.0),
+            10.0,
+            0.0,
+        );
+
+        assert_eq!(grid.find_path(Vec2::new(15.0, 15.0), Vec2::new(15.0, 85.0)), None);
+    }
+
+    #[test]
+    fn flow_field_leads_around_the_wall() {
+        let grid = walled_grid();
+        let goal = Vec2::new(85.0, 15.0);
+        let field = grid.flow_field(goal);
+
+        // Following the field from behind the wall ends up at the goal
+        let mut position = Vec2::new(15.0, 15.0);
+        for _ in 0..100 {
+            let Some(direction) = field.direction(position, goal) else {
+                panic!("no direction at {position}");
+            };
+            position += direction * 5.0;
+            assert!(grid.is_walkable(grid.cell_of(position)), "walked into the wall at {position}");
+        }
+        assert!(position.distance(goal) < 5.0);
+
+        // Nothing leads out of the wall
+        assert_eq!(field.direction(Vec2::new(45.0, 15.0), goal), None);
+    }
+
+    #[test]
+    fn navigator_replans_only_after_the_goal_moved() {
+        let mut navigator = Navigator::default();
+        navigator.set_goal(Some(Vec2::new(100.0, 0.0)));
+        assert!(navigator.needs_plan());
+
+        navigator.planned_for = navigator.goal;
+        navigator.set_goal(Some(Vec2::new(120.0, 0.0)));
+        assert!(!navigator.needs_plan());
+        navigator.set_goal(Some(Vec2::new(200.0, 0.0)));
+        assert!(navigator.needs_plan());
+    }
+
+    #[test]
+    fn navigator_drops_the_plan_for_an_old_goal() {
+        let pool = AsyncComputeTaskPool::get_or_init(TaskPool::new);
+        let mut navigator = Navigator::default();
+        navigator.set_goal(Some(Vec2::new(100.0, 0.0)));
+        navigator.planned_for = navigator.goal;
+        navigator.task = Some(pool.spawn(async { None }));
+
+        navigator.set_goal(Some(Vec2::new(110.0, 0.0)));
+        assert!(navigator.task.is_some());
+        navigator.set_goal(Some(Vec2::new(400.0, 0.0)));
+        assert!(navigator.task.is_none());
+
+        navigator.task = Some(pool.spawn(async { None }));
+        navigator.set_goal(None);
+        assert!(navigator.task.is_none());
+    }
+}
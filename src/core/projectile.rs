@@ -0,0 +1,207 @@
+use bevy::prelude::*;
+use crate::core::collision::{circle_rect_push, detect_contacts, rebuild_spatial_grid, StaticGrid};
+use crate::core::common::{AttackEvent, Collider, CollisionStarted, DamageType, Faction, FactionTable, Sensor, Stats, StatusEffect};
+
+/// Plugin moving projectiles and turning their collisions into attacks
+pub struct ProjectilePlugin;
+
+impl Plugin for ProjectilePlugin {
+    fn build(&self, app: &mut App) {
+        // Projectiles that expired or hit a wall are gone before their contacts are read
+        app.add_systems(Update, (
+            move_projectiles.before(rebuild_spatial_grid),
+            projectile_hits.after(detect_contacts).after(move_projectiles),
+        ));
+    }
+}
+
+/// What a projectile does once launched
+#[derive(Clone)]
+pub struct ProjectileSpec {
+    pub speed: f32,
+    /// Seconds before it disappears on its own
+    pub lifetime: f32,
+    /// How many targets it flies through before stopping at the next one
+    pub pierce: u32,
+    pub radius: f32,
+    pub damage: i32,
+    pub damage_type: DamageType,
+    pub effects: Vec<StatusEffect>,
+    /// Which colliders it touches (see `common::layers`), damage is only dealt to hostile factions
+    pub layer: u32,
+    pub mask: u32,
+    /// Direction the sprite art points in, radians from +X
+    pub sprite_angle: f32,
+}
+
+/// Flying projectile, hits are credited to `owner`. It keeps the owner's faction,
+/// so it still hits the right targets after the owner died.
+#[derive(Component)]
+pub struct Projectile {
+    pub owner: Entity,
+    pub faction: Faction,
+    pub velocity: Vec2,
+    pub lifetime: Timer,
+    pub pierce: u32,
+    pub damage: i32,
+    pub damage_type: DamageType,
+    pub effects: Vec<StatusEffect>,
+    /// Targets already hit, each one only once
+    hit: Vec<Entity>,
+}
+
+impl Projectile {
+    /// Records a hit on `target`. Returns false if the target was hit before
+    /// or the projectile already used up its piercing.
+    pub fn register_hit(&mut self, target: Entity) -> bool {
+        if self.is_spent() || self.hit.contains(&target) {
+            return false;
+        }
+
+        self.hit.push(target);
+        true
+    }
+
+    /// Hit as many targets as it can
+    pub fn is_spent(&self) -> bool {
+        self.hit.len() > self.pierce as usize
+    }
+}
+
+/// Spawns a projectile at `origin` flying along `direction`, the sprite is turned to face it
+pub fn launch_projectile(
+    commands: &mut Commands,
+    spec: &ProjectileSpec,
+    owner: Entity,
+    faction: Faction,
+    origin: Vec3,
+    direction: Vec2,
+    sprite: Sprite,
+) -> Entity {
+    let direction = direction.normalize_or(Vec2::X);
+
+    commands
+        .spawn((
+            Projectile {
+                owner,
+                faction,
+                velocity: direction * spec.speed,
+                lifetime: Timer::from_seconds(spec.lifetime, TimerMode::Once),
+                pierce: spec.pierce,
+                damage: spec.damage,
+                damage_type: spec.damage_type,
+                effects: spec.effects.clone(),
+                hit: Vec::new(),
+            },
+            Collider { radius: spec.radius, layer: spec.layer, mask: spec.mask },
+            Sensor,
+            sprite,
+            Transform::from_translation(origin).with_rotation(Quat::from_rotation_z(direction.to_angle() - spec.sprite_angle)),
+        ))
+        .id()
+}
+
+/// Flies projectiles forward, removes them when they run out of time or hit a wall
+fn move_projectiles(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Transform, &mut Projectile, &Collider)>,
+    walls: Res<StaticGrid>,
+    time: Res<Time>,
+) {
+    for (entity, mut transform, mut projectile, collider) in query.iter_mut() {
+        transform.translation += (projectile.velocity * time.delta_secs()).extend(0.0);
+
+        let center = transform.translation.truncate();
+        let bounds = Rect::from_center_half_size(center, Vec2::splat(collider.radius));
+        let hit_wall = walls
+            .query(bounds)
+            .into_iter()
+            .any(|i| circle_rect_push(center, collider.radius, walls.obstacles[i]).is_some());
+
+        if projectile.lifetime.tick(time.delta()).finished() || hit_wall {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Turns projectiles touching a target into `AttackEvent`s credited to their owner.
+/// Expired projectiles don't hit anything, `move_projectiles` already removed them.
+fn projectile_hits(
+    mut commands: Commands,
+    mut contacts: EventReader<CollisionStarted>,
+    mut projectiles: Query<&mut Projectile>,
+    targets: Query<&Faction, With<Stats>>,
+    factions: Res<FactionTable>,
+    mut attack_events: EventWriter<AttackEvent>,
+) {
+    for contact in contacts.read() {
+        // Sensors never touch each other, so at most one side is a projectile
+        let (entity, target) = if projectiles.contains(contact.a) {
+            (contact.a, contact.b)
+        } else {
+            (contact.b, contact.a)
+        };
+        let Ok(mut projectile) = projectiles.get_mut(entity) else {
+            continue;
+        };
+
+        let hostile = targets.get(target).is_ok_and(|faction| factions.is_hostile(projectile.faction, *faction));
+        let expired = projectile.lifetime.finished();
+        if target == projectile.owner || !hostile || expired || !projectile.register_hit(target) {
+            continue;
+        }
+
+        attack_events.write(AttackEvent {
+            attacker: projectile.owner,
+            target,
+            damage: projectile.damage,
+            damage_type: projectile.damage_type,
+            effects: projectile.effects.clone(),
+        });
+
+        // It may have hit a wall this frame as well
+        if projectile.is_spent() {
+            commands.entity(entity).try_despawn();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn projectile(pierce: u32) -> Projectile {
+        Projectile {
+            owner: Entity::from_raw(0),
+            faction: Faction::Player,
+            velocity: Vec2::X,
+            lifetime: Timer::from_seconds(1.0, TimerMode::Once),
+            pierce,
+            damage: 10,
+            damage_type: DamageType::Physical,
+            effects: Vec::new(),
+            hit: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn plain_projectile_stops_at_first_target() {
+        let mut arrow = projectile(0);
+
+        assert!(arrow.register_hit(Entity::from_raw(1)));
+        assert!(arrow.is_spent());
+        assert!(!arrow.register_hit(Entity::from_raw(2)));
+    }
+
+    #[test]
+    fn piercing_projectile_hits_each_target_once() {
+        let mut spear = projectile(2);
+
+        assert!(spear.register_hit(Entity::from_raw(1)));
+        assert!(!spear.register_hit(Entity::from_raw(1)));
+        assert!(spear.register_hit(Entity::from_raw(2)));
+        assert!(!spear.is_spent());
+        assert!(spear.register_hit(Entity::from_raw(3)));
+        assert!(spear.is_spent());
+    }
+}
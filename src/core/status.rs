@@ -0,0 +1,113 @@
+use bevy::prelude::*;
+use crate::core::combat::{detect_deaths, final_damage, handle_attack_events};
+use crate::core::common::{ApplyStatus, DamageDealt, Defense, Dying, Stats, StatusEffects};
+
+/// Plugin applying and ticking `StatusEffects`
+pub struct StatusPlugin;
+
+impl Plugin for StatusPlugin {
+    fn build(&self, app: &mut App) {
+        // Between landing hits and checking for deaths, so damage ticks can kill
+        app.add_event::<ApplyStatus>()
+            .add_systems(Update, (
+                apply_statuses,
+                tick_statuses,
+            ).chain().after(handle_attack_events).before(detect_deaths));
+    }
+}
+
+/// Puts the effects from `ApplyStatus` events on their targets
+pub fn apply_statuses(
+    mut events: EventReader<ApplyStatus>,
+    mut query: Query<&mut StatusEffects>,
+) {
+    for event in events.read() {
+        if let Ok(mut statuses) = query.get_mut(event.target) {
+            statuses.apply(event.effect, event.source);
+        }
+    }
+}
+
+/// Runs the effect timers and deals damage over time through the damage pipeline
+/// (resistances apply, i-frames and hit reactions don't)
+pub fn tick_statuses(
+    mut query: Query<(Entity, &mut StatusEffects, &mut Stats, Option<&Defense>), Without<Dying>>,
+    mut dealt: EventWriter<DamageDealt>,
+    time: Res<Time>,
+) {
+    for (entity, mut statuses, mut stats, defense) in query.iter_mut() {
+        if statuses.active.is_empty() {
+            continue;
+        }
+
+        for tick in statuses.tick(time.delta_secs()) {
+            let amount = final_damage(tick.damage, tick.damage_type, defense, None);
+            stats.hp -= amount;
+
+            dealt.write(DamageDealt {
+                attacker: tick.source,
+                target: entity,
+                amount,
+                damage_type: tick.damage_type,
+                critical: false,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::common::{DamageType, StatusEffect, StatusEffects, StatusKind, StatusTick};
+    use bevy::prelude::*;
+
+    fn poison() -> StatusEffect {
+        StatusEffect { kind: StatusKind::Poison, duration: 3.0, strength: 4.0, max_stacks: 3 }
+    }
+
+    #[test]
+    fn poison_ticks_every_second_until_it_runs_out() {
+        let source = Entity::from_raw(1);
+        let mut statuses = StatusEffects::default();
+        statuses.apply(poison(), source);
+
+        let ticks: Vec<StatusTick> = (0..10).flat_map(|_| statuses.tick(0.5)).collect();
+
+        assert_eq!(ticks.len(), 3);
+        assert_eq!(ticks[0], StatusTick { source, damage: 4, damage_type: DamageType::Poison });
+        assert!(statuses.active.is_empty());
+    }
+
+    #[test]
+    fn reapplying_stacks_and_refreshes() {
+        let mut statuses = StatusEffects::default();
+        for _ in 0..5 {
+            statuses.apply(poison(), Entity::from_raw(1));
+            statuses.tick(1.0);
+        }
+
+        // Capped at 3 stacks, refreshed to 3 seconds and 1 of them already gone
+        assert_eq!(statuses.active.len(), 1);
+        assert_eq!(statuses.active[0].stacks, 3);
+        assert_eq!(statuses.tick(0.5), vec![]);
+        assert_eq!(statuses.tick(0.5)[0].damage, 12);
+    }
+
+    #[test]
+    fn slow_and_stun_change_speed() {
+        let mut statuses = StatusEffects::default();
+        let slow = StatusEffect { kind: StatusKind::Slow, duration: 2.0, strength: 0.5, max_stacks: 2 };
+
+        statuses.apply(slow, Entity::from_raw(1));
+        assert_eq!(statuses.speed_multiplier(), 0.5);
+        statuses.apply(slow, Entity::from_raw(1));
+        assert_eq!(statuses.speed_multiplier(), 0.25);
+
+        statuses.apply(StatusEffect { kind: StatusKind::Stun, duration: 1.0, strength: 0.0, max_stacks: 1 }, Entity::from_raw(1));
+        assert_eq!(statuses.speed_multiplier(), 0.0);
+
+        // Stun wears off first
+        statuses.tick(1.0);
+        assert!(!statuses.is_stunned());
+        assert_eq!(statuses.speed_multiplier(), 0.25);
+    }
+}
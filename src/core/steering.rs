@@ -0,0 +1,138 @@
+use bevy::prelude::*;
+use crate::core::collision::{circle_rect_push, SpatialGrid, StaticGrid};
+use crate::core::common::{Collider, Velocity};
+use crate::core::movement::apply_velocity;
+
+/// Plugin turning the velocity AI units want into their actual `Velocity`
+pub struct SteeringPlugin;
+
+/// Neighbours closer than this (between edges) push each other apart
+const SEPARATION_GAP: f32 = 10.0;
+/// Speed at which crowded units spread out
+const SEPARATION_SPEED: f32 = 80.0;
+/// How far ahead, in seconds of movement, units look for walls
+const LOOK_AHEAD: f32 = 0.4;
+/// Speed at which units veer away from walls in front of them
+const AVOID_SPEED: f32 = 100.0;
+
+impl Plugin for SteeringPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, steer_units.before(apply_velocity));
+    }
+}
+
+/// Moves a unit through its `Velocity`. The AI sets `desired` every frame (see `arrive`),
+/// separation from neighbours and wall avoidance are added on top, and the velocity turns
+/// towards the result by at most `max_force` per second, so knockback still plays out.
+#[derive(Component)]
+#[require(Velocity)]
+pub struct Steering {
+    pub desired: Vec2,
+    pub max_force: f32,
+}
+
+impl Default for Steering {
+    fn default() -> Self {
+        Self { desired: Vec2::ZERO, max_force: 1500.0 }
+    }
+}
+
+/// Velocity heading along `direction` at `speed`, slowing down over the last `slow_radius`
+/// of the `remaining` distance so units don't overshoot their goal
+pub fn arrive(direction: Vec2, speed: f32, remaining: f32, slow_radius: f32) -> Vec2 {
+    direction * speed * (remaining / slow_radius).clamp(0.0, 1.0)
+}
+
+/// Push away from neighbours closer than `range`, stronger the closer they are, at most 1 long
+pub fn separation(position: Vec2, neighbours: impl IntoIterator<Item = Vec2>, range: f32) -> Vec2 {
+    neighbours
+        .into_iter()
+        .filter_map(|other| {
+            let away = position - other;
+            let distance = away.length();
+            // Units exactly on top of each other have no way to tell who goes where
+            (distance > f32::EPSILON && distance < range).then(|| away / distance * (1.0 - distance / range))
+        })
+        .sum::<Vec2>()
+        .clamp_length_max(1.0)
+}
+
+/// `velocity` turned towards `target` by at most `max_force * dt`
+pub fn steer_towards(velocity: Vec2, target: Vec2, max_force: f32, dt: f32) -> Vec2 {
+    velocity + (target - velocity).clamp_length_max(max_force * dt)
+}
+
+/// Adds separation and wall avoidance to what the AI wants and steers `Velocity` towards it
+pub fn steer_units(
+    mut units: Query<(Entity, &Transform, &Steering, &Collider, &mut Velocity)>,
+    grid: Res<SpatialGrid>,
+    walls: Res<StaticGrid>,
+    time: Res<Time>,
+) {
+    for (entity, transform, steering, collider, mut velocity) in units.iter_mut() {
+        let position = transform.translation.truncate();
+
+        // Cells are at least twice as wide as the biggest collider, so no neighbour
+        // within separation range is further out than this
+        let reach = collider.radius + grid.cell_size * 0.5 + SEPARATION_GAP;
+        let mut neighbours = Vec::new();
+        grid.for_each_within(transform.translation, reach, |i| {
+            let entry = &grid.entries[i];
+            // Everyone keeps some room, even units whose colliders pass through each other
+            if entry.entity != entity && !entry.sensor {
+                neighbours.push((entry.position.truncate(), entry.radius));
+            }
+        });
+        // Spread by edge distance, so big units keep more room around them
+        let push = neighbours
+            .iter()
+            .map(|(other, radius)| separation(position, [*other], collider.radius + radius + SEPARATION_GAP))
+            .sum::<Vec2>()
+            .clamp_length_max(1.0);
+
+        // Veer away from walls where it's heading
+        let ahead = position + steering.desired * LOOK_AHEAD;
+        let bounds = Rect::from_center_half_size(ahead, Vec2::splat(collider.radius));
+        let avoid = walls
+            .query(bounds)
+            .into_iter()
+            .filter_map(|i| circle_rect_push(ahead, collider.radius, walls.obstacles[i]))
+            .sum::<Vec2>()
+            .normalize_or_zero();
+
+        let target = steering.desired + push * SEPARATION_SPEED + avoid * AVOID_SPEED;
+        let steered = steer_towards(velocity.0.truncate(), target, steering.max_force, time.delta_secs());
+        velocity.0 = steered.extend(velocity.0.z);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arrival_slows_down_near_the_goal() {
+        assert_eq!(arrive(Vec2::X, 100.0, 500.0, 50.0), Vec2::new(100.0, 0.0));
+        assert_eq!(arrive(Vec2::X, 100.0, 25.0, 50.0), Vec2::new(50.0, 0.0));
+        assert_eq!(arrive(Vec2::X, 100.0, 0.0, 50.0), Vec2::ZERO);
+    }
+
+    #[test]
+    fn closer_neighbours_push_harder() {
+        let near = separation(Vec2::ZERO, [Vec2::new(10.0, 0.0)], 50.0);
+        let far = separation(Vec2::ZERO, [Vec2::new(40.0, 0.0)], 50.0);
+
+        assert!(near.x < far.x && far.x < 0.0);
+        assert_eq!(separation(Vec2::ZERO, [Vec2::new(60.0, 0.0)], 50.0), Vec2::ZERO);
+        // Surrounded on both sides, nowhere to go
+        assert_eq!(separation(Vec2::ZERO, [Vec2::new(10.0, 0.0), Vec2::new(-10.0, 0.0)], 50.0), Vec2::ZERO);
+    }
+
+    #[test]
+    fn velocity_turns_gradually() {
+        let turned = steer_towards(Vec2::new(100.0, 0.0), Vec2::new(0.0, 100.0), 600.0, 0.1);
+
+        assert!((turned - Vec2::new(100.0, 0.0)).length() <= 60.0 + 1e-3);
+        assert_eq!(steer_towards(Vec2::ZERO, Vec2::new(10.0, 0.0), 600.0, 0.1), Vec2::new(10.0, 0.0));
+    }
+}
@@ -0,0 +1,348 @@
+use bevy::{platform::collections::HashMap, prelude::*};
+use std::time::Duration;
+use crate::core::collision::{rebuild_spatial_grid, SpatialGrid};
+use crate::core::common::{DamageDealt, Dying, Faction, FactionTable, Stats, Target};
+
+/// Threat per second from a hostile standing right next to a unit, fading to 0 at the edge of sight
+const PROXIMITY_THREAT: f32 = 10.0;
+/// Fraction of threat lost per second once the fighting stops
+const THREAT_DECAY: f32 = 0.1;
+/// Threat below this is forgotten
+const MIN_THREAT: f32 = 0.01;
+/// A new target needs this much more threat than the current one to pull it away
+const SWITCH_MARGIN: f32 = 1.1;
+
+/// Plugin picking and dropping `Target`s for every unit with a `Perception`
+pub struct TargetingPlugin;
+
+impl Plugin for TargetingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Taunt>()
+            .add_systems(Update, (record_threat, update_threat.after(rebuild_spatial_grid), apply_taunts, drop_targets, acquire_targets).chain());
+    }
+}
+
+/// Which of the hostiles in sight a unit goes for
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TargetPriority {
+    Nearest,
+    /// Weakest target first, finishing off wounded units
+    LowestHp,
+    /// Whoever hurt this unit the most, see `ThreatTable`
+    Threat,
+}
+
+/// How an AI unit looks for targets. Hostile units (see `FactionTable`) within `sight`
+/// can become its `Target`, which is dropped again once it gets further than `leash`.
+#[derive(Component)]
+#[require(ThreatTable)]
+pub struct Perception {
+    pub sight: f32,
+    pub leash: f32,
+    pub priority: TargetPriority,
+    /// How often a unit that already has a target looks for a better one
+    pub reevaluate: Timer,
+    /// Inactive units keep their target but don't look for new ones
+    pub active: bool,
+}
+
+impl Perception {
+    pub fn new(sight: f32, leash: f32, priority: TargetPriority, reevaluate_secs: f32) -> Self {
+        Self {
+            sight,
+            leash,
+            priority,
+            reevaluate: Timer::from_seconds(reevaluate_secs, TimerMode::Repeating),
+            active: true,
+        }
+    }
+}
+
+/// How much each hostile threatens a unit, used by `TargetPriority::Threat`.
+/// Grows with damage taken and with hostiles standing close, decays over time.
+#[derive(Component, Default)]
+pub struct ThreatTable {
+    threat: HashMap<Entity, f32>,
+    /// Taunting entity, forced as the target until the timer runs out
+    taunt: Option<(Entity, Timer)>,
+}
+
+impl ThreatTable {
+    pub fn add(&mut self, source: Entity, amount: f32) {
+        *self.threat.entry(source).or_default() += amount;
+    }
+
+    pub fn threat_of(&self, source: Entity) -> f32 {
+        self.threat.get(&source).copied().unwrap_or(0.0)
+    }
+
+    /// Forces `source` as the target for `secs` and puts it at the top of the table,
+    /// so it stays the target for a while after the taunt ends
+    pub fn taunt(&mut self, source: Entity, secs: f32) {
+        let top = self.threat.values().copied().fold(0.0, f32::max);
+        self.threat.insert(source, top * SWITCH_MARGIN + 1.0);
+        self.taunt = Some((source, Timer::from_seconds(secs, TimerMode::Once)));
+    }
+
+    pub fn taunted_by(&self) -> Option<Entity> {
+        self.taunt.as_ref().map(|(source, _)| *source)
+    }
+
+    /// Decays threat and counts the taunt down
+    pub fn tick(&mut self, delta: Duration) {
+        let keep = (-THREAT_DECAY * delta.as_secs_f32()).exp();
+        self.threat.retain(|_, threat| {
+            *threat *= keep;
+            *threat >= MIN_THREAT
+        });
+
+        if self.taunt.as_mut().is_some_and(|(_, timer)| timer.tick(delta).finished()) {
+            self.taunt = None;
+        }
+    }
+}
+
+/// Makes every hostile unit with a `Perception` within `radius` of `source` go for it
+#[derive(Event)]
+pub struct Taunt {
+    pub source: Entity,
+    pub radius: f32,
+    pub duration: f32,
+}
+
+/// Threat per second from a hostile at `distance`
+pub fn proximity_threat(distance: f32, sight: f32) -> f32 {
+    PROXIMITY_THREAT * (1.0 - distance / sight).max(0.0)
+}
+
+/// Whether a target with `best` threat should replace the current one
+pub fn should_switch(current: f32, best: f32) -> bool {
+    best > current * SWITCH_MARGIN
+}
+
+/// Hostile unit in sight, as seen by the unit picking a target
+#[derive(Clone, Copy, Debug)]
+pub struct Candidate {
+    pub entity: Entity,
+    pub distance: f32,
+    pub hp: i32,
+    pub threat: f32,
+}
+
+/// Best candidate for the given priority, ties go to the nearest one
+pub fn pick_target(priority: TargetPriority, candidates: impl IntoIterator<Item = Candidate>) -> Option<Entity> {
+    candidates
+        .into_iter()
+        .min_by(|a, b| {
+            let first = match priority {
+                TargetPriority::Nearest => std::cmp::Ordering::Equal,
+                TargetPriority::LowestHp => a.hp.cmp(&b.hp),
+                TargetPriority::Threat => b.threat.total_cmp(&a.threat),
+            };
+            first.then(a.distance.total_cmp(&b.distance))
+        })
+        .map(|candidate| candidate.entity)
+}
+
+/// Credits damage to the attacker in the victim's `ThreatTable`
+fn record_threat(
+    mut dealt: EventReader<DamageDealt>,
+    mut tables: Query<&mut ThreatTable>,
+) {
+    for hit in dealt.read() {
+        if let Ok(mut table) = tables.get_mut(hit.target) {
+            table.add(hit.attacker, hit.amount as f32);
+        }
+    }
+}
+
+/// Everything `update_threat` reads from a unit keeping a threat table
+type Threatened<'a> = (Entity, &'a Transform, &'a Faction, &'a Perception, &'a mut ThreatTable);
+
+/// Builds threat from hostiles in sight and decays the rest.
+/// Only the colliders in the `SpatialGrid` cells within sight are looked at.
+fn update_threat(
+    mut units: Query<Threatened, Without<Dying>>,
+    others: Query<(&Transform, &Faction, &Stats), Without<Dying>>,
+    grid: Res<SpatialGrid>,
+    factions: Res<FactionTable>,
+    time: Res<Time>,
+) {
+    for (entity, transform, faction, perception, mut table) in units.iter_mut() {
+        table.tick(time.delta());
+
+        grid.for_each_within(transform.translation, perception.sight, |i| {
+            let other = grid.entries[i].entity;
+            let Ok((other_tf, other_faction, _)) = others.get(other) else {
+                return;
+            };
+            if other == entity || !factions.is_hostile(*faction, *other_faction) {
+                return;
+            }
+            let distance = transform.translation.distance(other_tf.translation);
+            let threat = proximity_threat(distance, perception.sight) * time.delta_secs();
+            if threat > 0.0 {
+                table.add(other, threat);
+            }
+        });
+    }
+}
+
+/// Hands `Taunt` events to the threat tables of hostile units around the taunter
+fn apply_taunts(
+    mut taunts: EventReader<Taunt>,
+    sources: Query<(&Transform, &Faction)>,
+    // Only units with a `Perception` keep a threat table
+    mut units: Query<(&Transform, &Faction, &mut ThreatTable), Without<Dying>>,
+    factions: Res<FactionTable>,
+) {
+    for taunt in taunts.read() {
+        let Ok((source_tf, source_faction)) = sources.get(taunt.source) else {
+            continue;
+        };
+
+        for (transform, faction, mut table) in units.iter_mut() {
+            if factions.is_hostile(*faction, *source_faction)
+                && transform.translation.distance(source_tf.translation) <= taunt.radius
+            {
+                table.taunt(taunt.source, taunt.duration);
+            }
+        }
+    }
+}
+
+/// Drops targets that died, despawned or got out of leash range
+fn drop_targets(
+    mut commands: Commands,
+    seekers: Query<(Entity, &Transform, &Perception, &Target)>,
+    targets: Query<&Transform, Without<Dying>>,
+) {
+    for (entity, transform, perception, target) in seekers.iter() {
+        let in_leash = targets
+            .get(target.target)
+            .is_ok_and(|target_tf| transform.translation.distance(target_tf.translation) <= perception.leash);
+
+        if !in_leash {
+            commands.entity(entity).remove::<Target>();
+        }
+    }
+}
+
+/// Everything `acquire_targets` reads from a unit looking for a target
+type Seeker<'a> = (Entity, &'a Transform, &'a Faction, &'a mut Perception, &'a ThreatTable, Option<&'a Target>);
+
+/// Gives units without a target the best hostile in sight. Units with a target
+/// switch to a better one every `Perception::reevaluate`, taunted units go straight
+/// for the taunter.
+fn acquire_targets(
+    mut commands: Commands,
+    mut seekers: Query<Seeker, Without<Dying>>,
+    candidates: Query<(Entity, &Transform, &Faction, &Stats), Without<Dying>>,
+    factions: Res<FactionTable>,
+    time: Res<Time>,
+) {
+    for (entity, transform, faction, mut perception, threat, current) in seekers.iter_mut() {
+        let due = perception.reevaluate.tick(time.delta()).just_finished();
+
+        if let Some(taunter) = threat.taunted_by().filter(|taunter| candidates.contains(*taunter)) {
+            if current.is_none_or(|target| target.target != taunter) {
+                commands.entity(entity).insert(Target { target: taunter });
+            }
+            continue;
+        }
+
+        if !perception.active || (current.is_some() && !due) {
+            continue;
+        }
+
+        let origin = transform.translation;
+        let in_sight = candidates
+            .iter()
+            .filter(|(other, _, other_faction, _)| *other != entity && factions.is_hostile(*faction, **other_faction))
+            .map(|(other, other_tf, _, stats)| Candidate {
+                entity: other,
+                distance: origin.distance(other_tf.translation),
+                hp: stats.hp,
+                threat: threat.threat_of(other),
+            })
+            .filter(|candidate| candidate.distance <= perception.sight);
+
+        // Threat based units don't flip between targets with about the same threat
+        let best = pick_target(perception.priority, in_sight).filter(|best| match current {
+            None => true,
+            Some(target) if perception.priority == TargetPriority::Threat => {
+                should_switch(threat.threat_of(target.target), threat.threat_of(*best))
+            }
+            Some(target) => target.target != *best,
+        });
+        if let Some(best) = best {
+            commands.entity(entity).insert(Target { target: best });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(id: u32, distance: f32, hp: i32, threat: f32) -> Candidate {
+        Candidate { entity: Entity::from_raw(id), distance, hp, threat }
+    }
+
+    fn crowd() -> Vec<Candidate> {
+        vec![
+            candidate(1, 100.0, 80, 0.0),
+            candidate(2, 300.0, 20, 10.0),
+            candidate(3, 200.0, 100, 50.0),
+        ]
+    }
+
+    #[test]
+    fn priorities_pick_different_targets() {
+        assert_eq!(pick_target(TargetPriority::Nearest, crowd()), Some(Entity::from_raw(1)));
+        assert_eq!(pick_target(TargetPriority::LowestHp, crowd()), Some(Entity::from_raw(2)));
+        assert_eq!(pick_target(TargetPriority::Threat, crowd()), Some(Entity::from_raw(3)));
+        assert_eq!(pick_target(TargetPriority::Nearest, Vec::new()), None);
+    }
+
+    #[test]
+    fn threat_decays_and_is_forgotten() {
+        let mut table = ThreatTable::default();
+        table.add(Entity::from_raw(1), 100.0);
+        table.add(Entity::from_raw(2), 0.01);
+
+        table.tick(Duration::from_secs(1));
+
+        assert!(table.threat_of(Entity::from_raw(1)) < 100.0);
+        assert!(table.threat_of(Entity::from_raw(1)) > 80.0);
+        assert_eq!(table.threat_of(Entity::from_raw(2)), 0.0);
+    }
+
+    #[test]
+    fn taunt_tops_the_table_until_it_wears_off() {
+        let mut table = ThreatTable::default();
+        table.add(Entity::from_raw(1), 200.0);
+
+        table.taunt(Entity::from_raw(2), 2.0);
+        assert_eq!(table.taunted_by(), Some(Entity::from_raw(2)));
+        assert!(should_switch(table.threat_of(Entity::from_raw(1)), table.threat_of(Entity::from_raw(2))));
+
+        table.tick(Duration::from_secs(3));
+        assert_eq!(table.taunted_by(), None);
+    }
+
+    #[test]
+    fn closer_hostiles_build_more_threat() {
+        assert!(proximity_threat(50.0, 500.0) > proximity_threat(400.0, 500.0));
+        assert_eq!(proximity_threat(600.0, 500.0), 0.0);
+        assert!(!should_switch(100.0, 105.0));
+        assert!(should_switch(100.0, 120.0));
+    }
+
+    #[test]
+    fn threat_falls_back_to_nearest() {
+        let strangers = vec![candidate(1, 250.0, 50, 0.0), candidate(2, 120.0, 50, 0.0)];
+
+        assert_eq!(pick_target(TargetPriority::Threat, strangers), Some(Entity::from_raw(2)));
+    }
+}
@@ -4,7 +4,7 @@ use bevy::{
     text::{FontSmoothing, LineHeight},
 };
 
-use crate::core::common::{Player, Stats};
+use crate::core::common::{GameState, Player, Stats};
 
 /// Plugin for GUI-related systems
 pub struct HudPlugin;
@@ -16,7 +16,8 @@ struct Hpbar;
 impl Plugin for HudPlugin {
     fn build(&self, app: &mut App) {
         // Add GUI setup and update systems
-        app.add_systems(Update, (setup, uptade_ui));
+        app.add_systems(Update, (setup, uptade_ui))
+            .add_systems(OnEnter(GameState::GameOver), show_game_over);
     }
 }
 
@@ -38,6 +39,32 @@ fn uptade_ui(
     }
 }
 
+/// Shows the game over banner once the player dies
+fn show_game_over(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    commands
+        .spawn(Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new("GAME OVER"),
+                TextFont {
+                    font: asset_server.load("fonts/Orbitron-Bold.ttf"),
+                    font_size: 80.0,
+                    ..default()
+                },
+                TextColor(Color::srgb(0.8, 0.0, 0.0)),
+            ));
+        });
+}
+
 /// Initializes the GUI (runs once)
 fn setup(
     mut commands: Commands,                             // Used to spawn UI entities
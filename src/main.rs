@@ -25,8 +25,19 @@ fn main() {
             core::animation::AnimationPlugin,
             core::collision::CollisionPlugin, 
             core::combat::CombatPlugin, 
+            core::hitbox::HitboxPlugin,
+            core::movement::MovementPlugin,
+            core::status::StatusPlugin,
+            core::projectile::ProjectilePlugin,
+            core::targeting::TargetingPlugin,
+            core::navigation::NavigationPlugin,
+            core::steering::SteeringPlugin,
+        ))
+        .add_plugins((
             world::minnions::minnion::MinnionsPlugin, 
             world::minnions::control::ControlMinnionsPlugin, 
+            world::minnions::recruit::RecruitPlugin,
+            world::minnions::unit::UnitPlugin,
             world::map::MapPlugin, 
             gui::hud::HudPlugin, 
             gui::inventory::InventoryPlugin, 
@@ -2,10 +2,14 @@ use bevy::platform::collections::HashMap;
 use bevy::prelude::*;
 
 use crate::core::common::{
-    Animation, AnimationIndices, AnimationSet, AnimationState, AttackEvent, Collider,
-    HitReactionTimer, InvincibilityTimer, Item, Player, Stats, Velocity,
+    Animation, AnimationIndices, AnimationSet, AnimationState, Collider,
+    HitReactionTimer, InvincibilityTimer, Item, Player, Stats, Velocity, layers, Body, BodyKind, Critical, DamageType, Faction, Knockback, GameState, StatusEffects,
 };
-use crate::world::enemy::Enemy;
+use crate::core::hitbox::{HitShape, HitboxFrames, Hitboxes, Hurtbox};
+use crate::core::projectile::{launch_projectile, ProjectileSpec};
+use crate::core::targeting::Taunt;
+use bevy::window::PrimaryWindow;
+use std::f32::consts::FRAC_PI_4;
 
 /// Main player plugin, sets up resources and systems
 pub struct PlayerPlugin;
@@ -16,6 +20,9 @@ pub struct Inventory {
     pub open: bool,
 }
 
+/// Money the player starts with, enough for a few recruits
+const STARTING_MONEY: u32 = 200;
+
 #[derive(Resource, Default)]
 pub struct PlayerGoodies {
     pub inv: Inventory,
@@ -35,6 +42,22 @@ pub struct PlayerAttackTimer {
     pub timer: Timer,
 }
 
+/// Dagger throw on `F`, aimed at the cursor
+#[derive(Component)]
+pub struct RangedSkill {
+    pub cooldown: Timer,
+    pub projectile: ProjectileSpec,
+}
+
+/// War cry on `T`, pulls nearby enemies onto the player
+#[derive(Component)]
+pub struct TauntSkill {
+    pub cooldown: Timer,
+    pub radius: f32,
+    /// Seconds the enemies are forced to attack the player
+    pub duration: f32,
+}
+
 /// Cardinal directions for animation logic
 #[derive(Clone)]
 enum Direction {
@@ -62,9 +85,9 @@ struct PlayerAnimationState {
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app
-            .insert_resource(PlayerGoodies { ..Default::default() })
+            .insert_resource(PlayerGoodies { money: STARTING_MONEY, ..Default::default() })
             .add_systems(Startup, spawn_player)
-            .add_systems(Update, (attack_player_system, control_player).chain());
+            .add_systems(Update, (tick_attack_timer, control_player, throw_dagger, taunt).chain().run_if(in_state(GameState::Playing)));
     }
 }
 
@@ -127,13 +150,67 @@ fn spawn_player(
         last_state: None,
     };
 
+    // Both skills can be used right away
+    let mut dagger_cooldown = Timer::from_seconds(1.0, TimerMode::Once);
+    dagger_cooldown.tick(dagger_cooldown.duration());
+    let mut taunt_cooldown = Timer::from_seconds(8.0, TimerMode::Once);
+    taunt_cooldown.tick(taunt_cooldown.duration());
+
     // Spawn player entity
     commands.spawn((
         Sprite::from_atlas_image(idle_down.clone(), TextureAtlas { layout: layout_8fps.clone(), index: 0 }),
         Player,
         Transform::from_scale(Vec3::splat(2.3)),
         Velocity(Vec3::ZERO),
-        Collider { radius: 30.0 },
+        Collider {
+            radius: 30.0,
+            layer: layers::PLAYER,
+            // Walks through own soldiers and NPC bodies (their masks skip the player),
+            // but still enters NPC interaction zones
+            mask: layers::ENEMY | layers::NPC,
+        },
+        // Heavier than a single orc, so a crowd can't shove the player around as easily
+        Body { mass: 4.0, kind: BodyKind::Dynamic },
+        // Sword swing in front of the player, on the middle frames of the attack
+        Hitboxes::for_states(
+            &[AnimationState::AttackUp, AnimationState::AttackDown, AnimationState::AttackLeft, AnimationState::AttackRight],
+            &[HitboxFrames {
+                first: 2,
+                last: 3,
+                shape: HitShape::Arc { radius: 110.0, half_angle: 60f32.to_radians() },
+            }],
+        ),
+        Hurtbox {
+            offset: Vec2::ZERO,
+            half_size: Vec2::new(24.0, 36.0),
+        },
+        Faction::Player,
+        // Offensive extras: crits, knockback, the dagger throw and the taunt
+        (
+            Critical { chance: 0.1, multiplier: 2.0 },
+            Knockback { impulse: 700.0 },
+            RangedSkill {
+                cooldown: dagger_cooldown,
+                projectile: ProjectileSpec {
+                    speed: 600.0,
+                    lifetime: 1.2,
+                    pierce: 1,
+                    radius: 12.0,
+                    damage: 20,
+                    damage_type: DamageType::Physical,
+                    effects: Vec::new(),
+                    layer: layers::PLAYER,
+                    mask: layers::ENEMY,
+                    // The dagger icon points up-right
+                    sprite_angle: FRAC_PI_4,
+                },
+            },
+            TauntSkill {
+                cooldown: taunt_cooldown,
+                radius: 300.0,
+                duration: 3.0,
+            },
+        ),
         PlayerAttackTimer {
             timer: Timer::from_seconds(0.320, TimerMode::Once),
         },
@@ -157,17 +234,18 @@ fn control_player(
     time: Res<Time>,
     keyboard: Res<ButtonInput<KeyCode>>,
     mut player_query: Query<(
-        &mut Velocity,
         &mut Transform,
         &mut Animation,
         &mut PlayerAttackTimer,
+        &StatusEffects,
     ), With<Player>>,
     mut camera_query: Query<&mut Transform, (With<Camera2d>, Without<Player>)>,
 ) {
-    if let Ok((mut velocity, mut player_transform, mut anim, mut attack_timer)) =
+    if let Ok((mut player_transform, mut anim, mut attack_timer, statuses)) =
         player_query.single_mut()
     {
-        velocity.0 = Vec3::ZERO;
+        // Walking direction from input, knockback goes through `Velocity` separately
+        let mut input = Vec3::ZERO;
         let mut new_state = anim.state;
 
         // Block movement during attack
@@ -180,28 +258,30 @@ fn control_player(
         ) && !attack_timer.timer.finished()
         {
             // Attacking - skip movement
+        } else if statuses.is_stunned() {
+            // Stunned - no input at all
         } else {
             // Move input
             if keyboard.pressed(KeyCode::KeyW) {
-                velocity.0.y += 1.0;
+                input.y += 1.0;
                 new_state = AnimationState::RunUp;
             }
             if keyboard.pressed(KeyCode::KeyS) {
-                velocity.0.y -= 1.0;
+                input.y -= 1.0;
                 new_state = AnimationState::RunDown;
             }
             if keyboard.pressed(KeyCode::KeyA) {
-                velocity.0.x -= 1.0;
+                input.x -= 1.0;
                 new_state = AnimationState::RunLeft;
             }
             if keyboard.pressed(KeyCode::KeyD) {
-                velocity.0.x += 1.0;
+                input.x += 1.0;
                 new_state = AnimationState::RunRight;
             }
         }
 
         // Attack input
-        if keyboard.pressed(KeyCode::Space) && attack_timer.timer.finished() {
+        if keyboard.pressed(KeyCode::Space) && attack_timer.timer.finished() && !statuses.is_stunned() {
             attack_timer.timer.reset();
             new_state = match anim.state {
                 AnimationState::RunUp | AnimationState::IdleUp => AnimationState::AttackUp,
@@ -210,8 +290,8 @@ fn control_player(
                 AnimationState::RunRight | AnimationState::IdleRight => AnimationState::AttackRight,
                 _ => AnimationState::AttackDown,
             };
-            velocity.0 = Vec3::ZERO; // Prevent movement during attack
-        } else if velocity.0.length_squared() == 0.0 {
+            input = Vec3::ZERO; // Prevent movement during attack
+        } else if input.length_squared() == 0.0 {
             // If not moving -> switch to idle
             new_state = match anim.state {
                 AnimationState::RunUp | AnimationState::AttackUp => AnimationState::IdleUp,
@@ -225,7 +305,7 @@ fn control_player(
         anim.state = new_state;
 
         // Move player
-        player_transform.translation += velocity.0 * time.delta_secs() * 200.0;
+        player_transform.translation += input * time.delta_secs() * 200.0 * statuses.speed_multiplier();
 
         // Follow camera
         if let Ok(mut camera_transform) = camera_query.single_mut() {
@@ -235,45 +315,81 @@ fn control_player(
     }
 }
 
-/// Detects if any enemy is hit when the player attacks
-fn attack_player_system(
-    mut p_query: Query<(Entity, &Stats, &Transform, &Animation, &mut PlayerAttackTimer), With<Player>>,
-    e_query: Query<(Entity, &Transform), With<Enemy>>,
-    mut attack_events: EventWriter<AttackEvent>,
+/// Ticks the attack cooldown. Damage is dealt by the `Hitboxes` of the attack animation
+fn tick_attack_timer(
+    mut p_query: Query<(&mut PlayerAttackTimer, &StatusEffects), With<Player>>,
     time: Res<Time>,
 ) {
-    if let Ok((player, p_stats, p_transform, p_anim_state, mut p_attack_timer)) =
-        p_query.single_mut()
-    {
-        p_attack_timer.timer.tick(time.delta());
+    if let Ok((mut p_attack_timer, statuses)) = p_query.single_mut() {
+        // Slows stretch the cooldown
+        p_attack_timer.timer.tick(time.delta().mul_f32(statuses.attack_speed_multiplier()));
+    }
+}
 
-        if !p_attack_timer.timer.just_finished() {
-            return;
-        }
+/// Throws a dagger towards the cursor when `F` is pressed and the skill is off cooldown
+fn throw_dagger(
+    mut commands: Commands,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    mut p_query: Query<(Entity, &Transform, &Faction, &StatusEffects, &mut RangedSkill), With<Player>>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+) {
+    let Ok((player, p_transform, faction, statuses, mut skill)) = p_query.single_mut() else {
+        return;
+    };
 
-        let player_translation = p_transform.translation;
-        let attack_range = 300.0;
-
-        // Determine where the attack should hit based on direction
-        let attack_offset = match p_anim_state.state {
-            AnimationState::AttackUp => Vec3::Y * attack_range,
-            AnimationState::AttackDown => -Vec3::Y * attack_range,
-            AnimationState::AttackLeft => -Vec3::X * attack_range,
-            AnimationState::AttackRight => Vec3::X * attack_range,
-            _ => Vec3::ZERO,
-        };
-
-        let attack_loc = player_translation + attack_offset;
-
-        // Check all enemies within attack range
-        for (enemy, e_transform) in e_query.iter() {
-            if e_transform.translation.distance(attack_loc) < attack_range {
-                attack_events.write(AttackEvent {
-                    attacker: player,
-                    target: enemy,
-                    damage: p_stats.attack,
-                });
-            }
-        }
+    skill.cooldown.tick(time.delta().mul_f32(statuses.attack_speed_multiplier()));
+    if !keyboard.just_pressed(KeyCode::KeyF) || !skill.cooldown.finished() || statuses.is_stunned() {
+        return;
     }
+
+    // Cursor position in world space
+    let Some(cursor_pos) = q_window
+        .single()
+        .ok()
+        .and_then(|w| w.cursor_position())
+        .and_then(|pos| {
+            let (cam, cam_tf) = q_camera.single().ok()?;
+            cam.viewport_to_world_2d(cam_tf, pos).ok()
+        })
+    else {
+        return;
+    };
+
+    skill.cooldown.reset();
+    launch_projectile(
+        &mut commands,
+        &skill.projectile,
+        player,
+        *faction,
+        p_transform.translation,
+        cursor_pos - p_transform.translation.truncate(),
+        Sprite {
+            image: asset_server.load("Gui/Inv_icons/dagger-icon.png"),
+            custom_size: Some(Vec2::splat(40.0)),
+            ..default()
+        },
+    );
+}
+
+/// Taunts the enemies around the player when `T` is pressed and the skill is off cooldown
+fn taunt(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+    mut p_query: Query<(Entity, &StatusEffects, &mut TauntSkill), With<Player>>,
+    mut taunts: EventWriter<Taunt>,
+) {
+    let Ok((player, statuses, mut skill)) = p_query.single_mut() else {
+        return;
+    };
+
+    skill.cooldown.tick(time.delta());
+    if !keyboard.just_pressed(KeyCode::KeyT) || !skill.cooldown.finished() || statuses.is_stunned() {
+        return;
+    }
+
+    skill.cooldown.reset();
+    taunts.write(Taunt { source: player, radius: skill.radius, duration: skill.duration });
 }
@@ -1,7 +1,7 @@
 use bevy::{ platform::collections::HashMap, prelude::*, window::PrimaryWindow };
 use crate::{core::common::{
-    Animation, AnimationIndices, AnimationSet, AnimationState, AttackEvent, Collider, HitReactionTimer, Player, Stats, Target
-}, world::minnions::minnion::Minnion};
+    Animation, AnimationIndices, AnimationSet, AnimationState, Collider, Defense, Dying, Faction, HitReactionTimer, Knockback, Stats, StatusEffects, Target, Velocity, layers
+}, core::hitbox::{start_swing, swinging, HitShape, HitboxFrames, Hitboxes, Hurtbox}, core::navigation::Navigator, core::steering::{arrive, steer_units, Steering}, core::targeting::{Perception, TargetPriority}};
 
 use std::time::Duration;
 
@@ -11,12 +11,71 @@ enum EnemyType {
     Wolf,
 }
 
-/// Component holding a timer for enemy attack cooldown
+/// Component holding a timer for enemy attack cooldown, a swing starts only once it's up
 #[derive(Component)]
 struct EnemyAttackTimer {
     timer: Timer,
 }
 
+/// Distance at which an enemy stops to swing at its target
+const ATTACK_RANGE: f32 = 110.0;
+/// Enemies at or below this fraction of their HP run away
+const FLEE_HP: f32 = 0.25;
+/// How close counts as being at the spawn point or a waypoint
+const HOME_RADIUS: f32 = 20.0;
+const WALK_SPEED: f32 = 100.0;
+const PATROL_SPEED: f32 = 60.0;
+const FLEE_SPEED: f32 = 130.0;
+/// Enemies slow down over this distance before reaching a waypoint or home
+const SLOW_RADIUS: f32 = 40.0;
+
+/// What an enemy is currently doing, drives its movement and animation
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum AiState {
+    /// Resting at the spawn point or a waypoint
+    #[default]
+    Idle,
+    /// Walking to the next waypoint
+    Patrol,
+    Chase,
+    Attack,
+    /// Running away from its target at low HP
+    Flee,
+    /// Walking back to the spawn point after losing its target
+    Return,
+}
+
+/// Spawn point and patrol route of an enemy
+#[derive(Component)]
+#[require(AiState)]
+pub struct Patrol {
+    pub spawn: Vec2,
+    pub waypoints: Vec<Vec2>,
+    next: usize,
+    /// How long it rests before walking on
+    rest: Timer,
+}
+
+impl Patrol {
+    /// Loop through the corners of a square around the spawn point
+    pub fn around(spawn: Vec2, half_size: f32, rest_secs: f32) -> Self {
+        Self {
+            spawn,
+            waypoints: [Vec2::new(-1.0, -1.0), Vec2::new(1.0, -1.0), Vec2::new(1.0, 1.0), Vec2::new(-1.0, 1.0)]
+                .into_iter()
+                .map(|corner| spawn + corner * half_size)
+                .collect(),
+            next: 0,
+            rest: Timer::from_seconds(rest_secs, TimerMode::Once),
+        }
+    }
+
+    /// Waypoint it is walking to
+    pub fn waypoint(&self) -> Option<Vec2> {
+        self.waypoints.get(self.next).copied()
+    }
+}
+
 /// Global timer resource used to limit how often enemies can be spawned
 #[derive(Resource)]
 struct EnemyTimer(Timer);
@@ -34,12 +93,8 @@ impl Plugin for EnemyPlugin {
         app.insert_resource(EnemyTimer(Timer::from_seconds(0.12, TimerMode::Repeating))) // Set enemy spawn rate
             .add_systems(Update, (
                 spawn_enemy,
-                find_enemy_target, 
-                move_enemies_tow_target, 
-                enemy_attack, 
-                hit_reaction, 
-                drop_target, 
-                change_animation_state
+                (update_ai_state, move_enemies.before(steer_units), enemy_attack, change_animation_state).chain(),
+                hit_reaction
             )); // Register systems
     }
 }
@@ -78,6 +133,7 @@ fn spawn_enemy(
     let atk_tex  = asset_server.load("Entities/Orc/Orc/Orc-Attack01.png");
     let idle_tex = asset_server.load("Entities/Orc/Orc/Orc-Idle.png");
     let hurt_tex = asset_server.load("Entities/Orc/Orc/Orc-Hurt.png");
+    let death_tex = asset_server.load("Entities/Orc/Orc/Orc-Death.png");
     
     let hurt_layout = texture_atlas_layouts.add(TextureAtlasLayout::from_grid(UVec2::splat(100), 4, 1, None, None));
   
@@ -90,6 +146,7 @@ fn spawn_enemy(
         (AnimationState::Walk,   (walk_tex.clone(), anim_layout.clone(), AnimationIndices { first: 0, last: 5 })),
         (AnimationState::Attack01, (atk_tex.clone(),  anim_layout.clone(), AnimationIndices { first: 0, last: 5 })),
         (AnimationState::Hurt,   (hurt_tex.clone(), hurt_layout.clone(), AnimationIndices { first: 0, last: 1 })),
+        (AnimationState::Death,  (death_tex.clone(), hurt_layout.clone(), AnimationIndices { first: 0, last: 3 })),
     ]);
 
     let animation_set = AnimationSet{ animations: anim_map };
@@ -100,6 +157,12 @@ fn spawn_enemy(
 
     hit_timer.timer.tick(Duration::from_secs_f32(0.2));
 
+    // Ready to swing right away
+    let mut attack_timer = EnemyAttackTimer {
+        timer: Timer::from_seconds(0.6, TimerMode::Once),
+    };
+    attack_timer.timer.tick(Duration::from_secs_f32(0.6));
+
     let animation = Animation {
         set: animation_set,
         state: AnimationState::Idle,
@@ -122,10 +185,38 @@ fn spawn_enemy(
             z: 0.0,
         }),
         animation,
-        Collider { radius: 22.0 },
-        EnemyAttackTimer {
-            timer: Timer::from_seconds(0.6, TimerMode::Repeating),
+        Collider {
+            radius: 22.0,
+            layer: layers::ENEMY,
+            mask: layers::PLAYER | layers::MINION | layers::ENEMY | layers::NPC,
         },
+        attack_timer,
+        // Axe chop in front of the orc when the swing lands
+        Hitboxes::for_states(
+            &[AnimationState::Attack01],
+            &[HitboxFrames {
+                first: 3,
+                last: 4,
+                shape: HitShape::Rect { offset: Vec2::new(45.0, 0.0), half_size: Vec2::new(35.0, 30.0) },
+            }],
+        ),
+        Hurtbox {
+            offset: Vec2::ZERO,
+            half_size: Vec2::new(36.0, 44.0),
+        },
+        (
+            Faction::Orcs,
+            // Goes for whoever hurts it the most, the player can pull orcs off soldiers
+            Perception::new(500.0, 550.0, TargetPriority::Threat, 0.5),
+            // Walks a small loop around where it was put down
+            Patrol::around(cursor_pos, 150.0, 2.0),
+            Navigator::default(),
+            Steering::default(),
+        ),
+        // Thick hide, shrugs off a bit of every blow
+        Defense { armor: 10, ..default() },
+        Knockback { impulse: 500.0 },
+        Velocity::default(),
         Stats { 
             hp: 100,
             max_hp: 100,
@@ -138,165 +229,211 @@ fn spawn_enemy(
 }
 
 
-/// System that moves enemies towards their target (if any),
-/// as long as they are not currently under attack.
-fn move_enemies_tow_target(
-    mut enemies: Query<(&mut Transform, &EnemyAttackTimer, Option<&Target>), With<Enemy>>,
-    targets: Query<&Transform, Without<Enemy>>,
-    time: Res<Time>,
-) {
-    for (mut enemy_tf, attack_timer, maybe_target) in enemies.iter_mut() {
-        // If the enemy has a target assigned
-        if let Some(target) = maybe_target {
-            if let Ok(target_tf) = targets.get(target.target) {
-                // It only moves if it doesn't attack
-                if !attack_timer.timer.finished() {
-                    let direction = (target_tf.translation - enemy_tf.translation).normalize_or_zero();
-                    
-                    // Movement towards the target
-                    enemy_tf.translation += direction * time.delta_secs() * 100.0;
-
-                    // Rotate the sprite towards the target
-                    if direction.x.abs() > 0.1 {
-                        enemy_tf.scale.x = direction.x.signum() * 4.0;
-                    }
-                }
-            }
-        }
+/// Senses an enemy decides its next `AiState` from
+#[derive(Clone, Copy, Debug)]
+pub struct AiSenses {
+    /// Distance to the current target, if it has one
+    pub target_distance: Option<f32>,
+    /// Current HP over max HP
+    pub hp_ratio: f32,
+    pub home_distance: f32,
+    pub at_waypoint: bool,
+    /// Done resting at the current spot
+    pub rested: bool,
+    pub has_waypoints: bool,
+}
+
+/// Transitions of the enemy state machine
+pub fn next_ai_state(state: AiState, senses: AiSenses) -> AiState {
+    match senses.target_distance {
+        // Wounded enemies run for it, and keep running until they lose the target
+        Some(_) if state == AiState::Flee || senses.hp_ratio <= FLEE_HP => AiState::Flee,
+        Some(distance) if distance <= ATTACK_RANGE => AiState::Attack,
+        Some(_) => AiState::Chase,
+        None => match state {
+            AiState::Chase | AiState::Attack | AiState::Flee | AiState::Return if senses.home_distance > HOME_RADIUS => AiState::Return,
+            AiState::Chase | AiState::Attack | AiState::Flee | AiState::Return => AiState::Idle,
+            AiState::Patrol if senses.at_waypoint => AiState::Idle,
+            AiState::Idle if senses.rested && senses.has_waypoints => AiState::Patrol,
+            other => other,
+        },
     }
 }
 
+/// Everything `update_ai_state` reads from an enemy
+type Brain<'a> = (&'a mut AiState, &'a mut Patrol, &'a Transform, &'a Stats, Option<&'a Target>);
 
-// Finds target for enemy 
-fn find_enemy_target(
-    enemies: Query<(Entity, &Transform), (With<Enemy>, (Without<Player>, Without<Minnion>, Without<Target>))>,
-    targets: Query<(Entity, &Transform), (Or<(With<Player>, With<Minnion>)>, Without<Enemy>)>,
-    mut commands: Commands,
+/// Moves every enemy's `AiState` along, see `next_ai_state`
+fn update_ai_state(
+    mut enemies: Query<Brain, (With<Enemy>, Without<Dying>)>,
+    targets: Query<&GlobalTransform>,
+    time: Res<Time>,
 ) {
-    for (enemy, enemy_tf) in enemies.iter() {
-        let mut closest_target: Option<(Entity, f32)> = None;
-
-        for (target, target_tf) in targets.iter() {
-            let dist = enemy_tf.translation.distance(target_tf.translation);
-            if dist < 500.0 {
-                match closest_target {
-                    Some((_, closest_dist)) if dist < closest_dist => {
-                        closest_target = Some((target, dist));
-                    }
-                    None => {
-                        closest_target = Some((target, dist));
-                    }
-                    _ => {}
-                }
-            }
+    for (mut state, mut patrol, transform, stats, maybe_target) in enemies.iter_mut() {
+        let position = transform.translation.truncate();
+        patrol.rest.tick(time.delta());
+
+        let senses = AiSenses {
+            target_distance: maybe_target
+                .and_then(|target| targets.get(target.target).ok())
+                .map(|target_tf| position.distance(target_tf.translation().truncate())),
+            hp_ratio: stats.hp as f32 / stats.max_hp as f32,
+            home_distance: position.distance(patrol.spawn),
+            at_waypoint: patrol.waypoint().is_some_and(|waypoint| position.distance(waypoint) <= HOME_RADIUS),
+            rested: patrol.rest.finished(),
+            has_waypoints: !patrol.waypoints.is_empty(),
+        };
+
+        let next = next_ai_state(*state, senses);
+        if next == *state {
+            continue;
         }
 
-        if let Some((ent, _)) = closest_target {
-            commands.entity(enemy).insert(Target { target: ent });
-        } else {
-            commands.entity(enemy).remove::<Target>();
+        // Rest a bit at every waypoint before heading to the next one
+        if *state == AiState::Patrol && next == AiState::Idle {
+            patrol.next = (patrol.next + 1) % patrol.waypoints.len();
+        }
+        if next == AiState::Idle {
+            patrol.rest.reset();
         }
+        *state = next;
     }
 }
 
 
-// Drops target when distanse id greater than 550
-fn drop_target(
-    mut query: Query<(Entity, &mut Target, &Transform), With<Enemy>>,
-    targets: Query<&Transform>,
-    mut commands: Commands
-) {
-    for (minnion_entity, target, tf) in query.iter_mut() {
+/// Everything `move_enemies` reads from an enemy
+type Walker<'a> = (&'a mut Transform, &'a mut Navigator, &'a mut Steering, &'a AiState, &'a Patrol, &'a StatusEffects, Option<&'a Target>);
 
-        match targets.get(target.target) {
-            Ok(target_tf) => {
-                if tf.translation.distance(target_tf.translation) > 550.0 {
-                    commands.entity(minnion_entity).remove::<Target>();
-                }
-            }
-            Err(_) => {
-                commands.entity(minnion_entity).remove::<Target>();
-            }
+/// Moves enemies according to their `AiState`: towards the target, away from it,
+/// back to the spawn point or along the patrol route. Walking goes around walls through the `Navigator`,
+/// the resulting velocity goes to `Steering`.
+fn move_enemies(
+    mut enemies: Query<Walker, (With<Enemy>, Without<Dying>)>,
+    targets: Query<&GlobalTransform>,
+) {
+    for (mut enemy_tf, mut navigator, mut steering, state, patrol, statuses, maybe_target) in enemies.iter_mut() {
+        let position = enemy_tf.translation.truncate();
+        let target_pos = maybe_target
+            .and_then(|target| targets.get(target.target).ok())
+            .map(|target_tf| target_tf.translation().truncate());
+
+        let (goal, speed) = match (*state, target_pos) {
+            (AiState::Chase, Some(target)) => (Some(target), WALK_SPEED),
+            (AiState::Return, _) => (Some(patrol.spawn), WALK_SPEED),
+            (AiState::Patrol, _) => (patrol.waypoint(), PATROL_SPEED),
+            _ => (None, 0.0),
+        };
+        navigator.set_goal(goal);
+
+        let (direction, speed) = match (*state, target_pos) {
+            // Runs straight away from the target
+            (AiState::Flee, Some(target)) => ((position - target).normalize_or_zero(), FLEE_SPEED),
+            // Keeps facing the target while swinging
+            (AiState::Attack, Some(target)) => ((target - position).normalize_or_zero(), 0.0),
+            _ => (navigator.steer(position, HOME_RADIUS), speed),
+        };
+
+        let remaining = goal.map_or(f32::INFINITY, |goal| position.distance(goal));
+        steering.desired = arrive(direction, speed * statuses.speed_multiplier(), remaining, SLOW_RADIUS);
+
+        // Rotate the sprite towards where it's going
+        if direction.x.abs() > 0.1 {
+            enemy_tf.scale.x = direction.x.signum() * 4.0;
         }
     }
 }
 
 
-/// Handles enemy attack logic when in range of the player
+/// Ticks the attack cooldown. Damage is dealt by the `Hitboxes` of the attack animation,
+/// which `change_animation_state` only starts once the cooldown is up
 fn enemy_attack(
-    mut enemy_q: Query<(Entity, &Transform, &mut EnemyAttackTimer, &HitReactionTimer, Option<&Target>), With<Enemy>>,
-    targets_q: Query<&Transform, Without<Enemy>>,
+    mut enemy_q: Query<(&mut EnemyAttackTimer, &StatusEffects), With<Enemy>>,
     time: Res<Time>,
-    mut attack_events: EventWriter<AttackEvent>,
 ) {
 
-    for (enemy_ent, enemy_tf, mut cooldown, hit_timer, maybe_target) in enemy_q.iter_mut() {
-
-        if let Some(target) = maybe_target {
-            cooldown.timer.tick(time.delta());
-            if let Ok(target_tf) = targets_q.get(target.target) {
-                let dist = enemy_tf.translation.distance(target_tf.translation);
-
-                if hit_timer.timer.finished() {
-                    if dist < 110.  {
-
-                        if cooldown.timer.just_finished() {
-                            attack_events.write(AttackEvent {
-                                attacker: enemy_ent,
-                                target: target.target,
-                                damage: 30,
-                            });
-                            cooldown.timer.reset();
-                        }
-                    }
-                }
-            }
-        }
-        
+    for (mut cooldown, statuses) in enemy_q.iter_mut() {
+        cooldown.timer.tick(time.delta().mul_f32(statuses.attack_speed_multiplier()));
     }
     
 }
 
+/// Picks the animation from the `AiState`, getting hit interrupts everything.
+/// Attacking enemies play one swing per attack cooldown and stand idle in between.
 fn change_animation_state(
-    q: Query<(&HitReactionTimer, &EnemyAttackTimer, &mut Animation, &Transform, Option<&Target>), With<Enemy>>,
-    targets_q: Query<&Transform, (Or<(With<Player>, With<Minnion>)>, Without<Enemy>)>
+    q: Query<(&HitReactionTimer, &AiState, &mut EnemyAttackTimer, &mut Animation, &Sprite), (With<Enemy>, Without<Dying>)>,
 ) {
 
-    for (hit_timer, att_timer,mut anim, tf,  maybe_target) in q {
-        if !hit_timer.timer.finished() {
-            anim.state = AnimationState::Hurt;
-            continue;
-        }
-
-        if let Some(target) = maybe_target {
-            if let Ok(target_tf) = targets_q.get(target.target) {
-
-                if tf.translation.distance(target_tf.translation) < 110. && !att_timer.timer.finished(){
-                    anim.state = AnimationState::Attack01;
-                    continue;
-                } else {
-                    anim.state = AnimationState::Walk;
-                    continue;
+    for (hit_timer, state, mut cooldown, mut anim, sprite) in q {
+        anim.state = if !hit_timer.timer.finished() {
+            AnimationState::Hurt
+        } else {
+            match state {
+                AiState::Idle => AnimationState::Idle,
+                AiState::Attack => {
+                    let swinging = swinging(&anim, sprite, AnimationState::Attack01);
+                    if swinging || start_swing(&mut cooldown.timer, swinging) {
+                        AnimationState::Attack01
+                    } else {
+                        AnimationState::Idle
+                    }
                 }
+                AiState::Patrol | AiState::Chase | AiState::Flee | AiState::Return => AnimationState::Walk,
             }
-        }
-
-        anim.state = AnimationState::Idle;
+        };
     }
 }
 
 
+/// Counts down the hurt reaction. Death is handled by `core::combat`
 fn hit_reaction(
-    mut commands: Commands,
-    mut query: Query<(Entity, &Stats, &mut HitReactionTimer), With<Enemy>>,
+    mut query: Query<&mut HitReactionTimer, With<Enemy>>,
     time: Res<Time>,
 ) {
-    for (enemy, stats, mut reaction_timer) in query.iter_mut() {
+    for mut reaction_timer in query.iter_mut() {
         reaction_timer.timer.tick(time.delta());
+    }
+}
 
-        if stats.hp <= 0 {
-            commands.entity(enemy).despawn();
-            continue;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn senses() -> AiSenses {
+        AiSenses {
+            target_distance: None,
+            hp_ratio: 1.0,
+            home_distance: 0.0,
+            at_waypoint: false,
+            rested: false,
+            has_waypoints: true,
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn chases_then_attacks_a_target() {
+        let far = AiSenses { target_distance: Some(300.0), ..senses() };
+        let close = AiSenses { target_distance: Some(80.0), ..senses() };
+
+        assert_eq!(next_ai_state(AiState::Patrol, far), AiState::Chase);
+        assert_eq!(next_ai_state(AiState::Chase, close), AiState::Attack);
+        assert_eq!(next_ai_state(AiState::Attack, far), AiState::Chase);
+    }
+
+    #[test]
+    fn flees_at_low_hp_until_the_target_is_lost() {
+        let wounded = AiSenses { target_distance: Some(80.0), hp_ratio: 0.2, ..senses() };
+        let lost = AiSenses { home_distance: 400.0, ..senses() };
+
+        assert_eq!(next_ai_state(AiState::Attack, wounded), AiState::Flee);
+        assert_eq!(next_ai_state(AiState::Flee, lost), AiState::Return);
+        assert_eq!(next_ai_state(AiState::Return, senses()), AiState::Idle);
+    }
+
+    #[test]
+    fn rests_between_waypoints() {
+        assert_eq!(next_ai_state(AiState::Idle, senses()), AiState::Idle);
+        assert_eq!(next_ai_state(AiState::Idle, AiSenses { rested: true, ..senses() }), AiState::Patrol);
+        assert_eq!(next_ai_state(AiState::Patrol, AiSenses { at_waypoint: true, ..senses() }), AiState::Idle);
+        assert_eq!(next_ai_state(AiState::Idle, AiSenses { rested: true, has_waypoints: false, ..senses() }), AiState::Idle);
+    }
+}
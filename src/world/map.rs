@@ -1,10 +1,29 @@
 use bevy::prelude::*;
 use bevy_ecs_tiled::prelude::*;
+use bevy_ecs_tilemap::prelude::TilemapTileSize;
+use tiled::{LayerType, ObjectShape};
+
+use crate::core::common::StaticCollider;
+use crate::core::navigation::NavGrid;
+
 pub struct MapPlugin;
 
+/// Object layers whose name contains this (case insensitive) are turned into static colliders
+const COLLISION_LAYER_NAME: &str = "collision";
+
+/// Size of a navigation cell in world units, half a map tile
+const NAV_CELL_SIZE: f32 = 40.0;
+/// Room kept between paths and walls, about the radius of a unit
+const NAV_CLEARANCE: f32 = 24.0;
+
+/// Marker for static colliders generated from the Tiled map
+#[derive(Component)]
+pub struct MapCollider;
+
 impl Plugin for MapPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, load_map);
+        app.add_systems(Startup, load_map)
+            .add_systems(Update, spawn_map_colliders);
     }
 }
 
@@ -23,3 +42,272 @@ fn load_map(
         },
     ));
 }
+
+/// Turns collision objects and per-tile collision shapes of a freshly spawned map into `StaticCollider`s,
+/// and builds the `NavGrid` around them
+fn spawn_map_colliders(
+    mut commands: Commands,
+    mut map_events: EventReader<TiledMapCreated>,
+    map_assets: Res<Assets<TiledMap>>,
+    q_maps: Query<(&Transform, &TilemapAnchor)>,
+    q_old: Query<Entity, With<MapCollider>>,
+) {
+    for event in map_events.read() {
+        let Some(tiled_map) = event.get_map_asset(&map_assets) else {
+            continue;
+        };
+        let Ok((map_tf, anchor)) = q_maps.get(event.entity) else {
+            continue;
+        };
+
+        // Map got (re)spawned, drop colliders from the previous load
+        for old in q_old.iter() {
+            commands.entity(old).despawn();
+        }
+
+        // Rectangles in map-local space (before the map transform is applied)
+        let mut rects: Vec<Rect> = Vec::new();
+
+        let grid_size = get_grid_size(&tiled_map.map);
+        let tile_size = TilemapTileSize { x: grid_size.x, y: grid_size.y };
+        let map_type = get_map_type(&tiled_map.map);
+
+        for layer in tiled_map.map.layers() {
+            match layer.layer_type() {
+                // Every object on a collision layer becomes an obstacle
+                LayerType::Objects(object_layer) => {
+                    if !layer.name.to_lowercase().contains(COLLISION_LAYER_NAME) {
+                        continue;
+                    }
+
+                    for object in object_layer.objects() {
+                        if let Some((min, max)) = shape_bounds(&object.shape, object.x, object.y, object.rotation) {
+                            // Tiled Y axis points down, so these are the top-left and bottom-right corners
+                            let a = from_tiled_position_to_world_space(tiled_map, anchor, min);
+                            let b = from_tiled_position_to_world_space(tiled_map, anchor, max);
+                            rects.push(Rect::from_corners(a, b));
+                        }
+                    }
+                }
+                // Tiles carry their own collision shapes defined in the tileset
+                LayerType::Tiles(tile_layer) => {
+                    for_each_tile(tiled_map, &tile_layer, |layer_tile, _, tile_pos, _| {
+                        let Some(tile) = layer_tile.get_tile() else {
+                            return;
+                        };
+                        let Some(collision) = &tile.collision else {
+                            return;
+                        };
+
+                        let center = tile_pos.center_in_world(
+                            &tiled_map.tilemap_size,
+                            &grid_size,
+                            &tile_size,
+                            &map_type,
+                            anchor,
+                        );
+
+                        // Collision shapes are relative to the tile image, which Tiled draws
+                        // anchored to the bottom-left of the cell when it is bigger than the grid
+                        let image_size = tile
+                            .image
+                            .as_ref()
+                            .map(|image| Vec2::new(image.width as f32, image.height as f32))
+                            .unwrap_or_else(|| {
+                                let tileset = tile.tileset();
+                                Vec2::new(tileset.tile_width as f32, tileset.tile_height as f32)
+                            });
+                        let bottom_left = center - Vec2::new(grid_size.x, grid_size.y) / 2.0;
+                        let top_left = bottom_left + Vec2::new(0.0, image_size.y);
+
+                        for object in collision.object_data() {
+                            if let Some((min, max)) = shape_bounds(&object.shape, object.x, object.y, object.rotation) {
+                                rects.push(Rect::from_corners(
+                                    top_left + Vec2::new(min.x, -min.y),
+                                    top_left + Vec2::new(max.x, -max.y),
+                                ));
+                            }
+                        }
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        let mut obstacles = Vec::with_capacity(rects.len());
+        for rect in rects {
+            let (translation, half_size) = map_rect_to_world(map_tf, rect);
+            obstacles.push(Rect::from_center_half_size(translation.truncate(), half_size));
+
+            commands.spawn((
+                StaticCollider { half_size },
+                Transform::from_translation(translation),
+                MapCollider,
+            ));
+        }
+
+        // The walls enclose the playable area, so their bounds cover everything walkable
+        if let Some(bounds) = obstacles.iter().copied().reduce(|a, b| a.union(b)) {
+            commands.insert_resource(NavGrid::from_obstacles(&obstacles, bounds, NAV_CELL_SIZE, NAV_CLEARANCE));
+        }
+    }
+}
+
+/// Applies the map transform (scale and offset) to a map-local rectangle.
+/// The map sits far behind everything on Z, the collider goes on the gameplay plane (z = 0).
+fn map_rect_to_world(map_tf: &Transform, rect: Rect) -> (Vec3, Vec2) {
+    let center = map_tf.transform_point(rect.center().extend(0.0));
+    let half_size = rect.half_size() * map_tf.scale.truncate().abs();
+
+    (center.with_z(0.0), half_size)
+}
+
+/// Bounding box of a Tiled shape in Tiled coordinates (Y pointing down).
+///
+/// Polygons are approximated by their bounding box. `StaticCollider` is axis-aligned,
+/// so only rotations by multiples of 90 degrees can be represented; other rotations
+/// are skipped with a warning, as do points and text, which have no area.
+fn shape_bounds(shape: &ObjectShape, x: f32, y: f32, rotation: f32) -> Option<(Vec2, Vec2)> {
+    let origin = Vec2::new(x, y);
+
+    // Shape outline relative to the object origin
+    let points: Vec<Vec2> = match shape {
+        ObjectShape::Rect { width, height } | ObjectShape::Ellipse { width, height } => vec![
+            Vec2::ZERO,
+            Vec2::new(*width, 0.0),
+            Vec2::new(0.0, *height),
+            Vec2::new(*width, *height),
+        ],
+        ObjectShape::Polygon { points } | ObjectShape::Polyline { points } => {
+            points.iter().map(|(px, py)| Vec2::new(*px, *py)).collect()
+        }
+        _ => return None,
+    };
+
+    if points.is_empty() {
+        return None;
+    }
+
+    let quarter_turns = rotation / 90.0;
+    if (quarter_turns - quarter_turns.round()).abs() > 0.001 {
+        warn!("Skipping collision object rotated by {rotation} degrees, only multiples of 90 are supported");
+        return None;
+    }
+
+    // Tiled rotates clockwise around the object origin, with Y pointing down
+    let rotate = Vec2::from_angle(rotation.to_radians());
+    let mut min = Vec2::splat(f32::MAX);
+    let mut max = Vec2::splat(f32::MIN);
+    for point in points {
+        let rotated = rotate.rotate(point);
+        min = min.min(rotated);
+        max = max.max(rotated);
+    }
+
+    Some((origin + min, origin + max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_transform_is_applied() {
+        let map_tf = Transform {
+            translation: Vec3::new(0., 0., -1000.0),
+            scale: Vec3::splat(2.5),
+            ..default()
+        };
+
+        let (center, half_size) = map_rect_to_world(&map_tf, Rect::new(-16.0, 0.0, 16.0, 64.0));
+
+        assert_eq!(center, Vec3::new(0.0, 80.0, 0.0));
+        assert_eq!(half_size, Vec2::new(40.0, 80.0));
+    }
+
+    #[test]
+    fn shipped_map_walls_enclose_every_tile() {
+        let map = tiled::Loader::new()
+            .load_tmx_map("assets/Maps/untitled.tmx")
+            .expect("map should load");
+
+        // Wall rectangles in Tiled pixels
+        let walls: Vec<Rect> = map
+            .layers()
+            .filter(|layer| layer.name.to_lowercase().contains(COLLISION_LAYER_NAME))
+            .filter_map(|layer| layer.as_object_layer())
+            .flat_map(|layer| layer.objects().collect::<Vec<_>>())
+            .filter_map(|object| shape_bounds(&object.shape, object.x, object.y, object.rotation))
+            .map(|(min, max)| Rect::from_corners(min, max))
+            .collect();
+        assert_eq!(walls.len(), 4);
+
+        // Area enclosed by the walls
+        let inner = Rect::from_corners(
+            Vec2::new(walls.iter().map(|w| w.min.x).fold(f32::MAX, f32::min), walls.iter().map(|w| w.min.y).fold(f32::MAX, f32::min)),
+            Vec2::new(walls.iter().map(|w| w.max.x).fold(f32::MIN, f32::max), walls.iter().map(|w| w.max.y).fold(f32::MIN, f32::max)),
+        )
+        .inflate(-32.0);
+
+        let Some(tiled::TileLayer::Infinite(tiles)) = map.layers().find_map(|layer| layer.as_tile_layer()) else {
+            panic!("map should have an infinite tile layer");
+        };
+
+        for (chunk_pos, chunk) in tiles.chunks() {
+            for x in 0..tiled::ChunkData::WIDTH as i32 {
+                for y in 0..tiled::ChunkData::HEIGHT as i32 {
+                    if chunk.get_tile(x, y).is_none() {
+                        continue;
+                    }
+                    let px = Vec2::new(
+                        ((chunk_pos.0 * tiled::ChunkData::WIDTH as i32 + x) * 32) as f32,
+                        ((chunk_pos.1 * tiled::ChunkData::HEIGHT as i32 + y) * 32) as f32,
+                    );
+                    let tile = Rect::from_corners(px, px + Vec2::splat(32.0));
+
+                    // Every painted tile is walkable and inside the walls
+                    assert!(inner.contains(tile.min) && inner.contains(tile.max), "tile at {px} outside walls");
+                    assert!(walls.iter().all(|w| w.intersect(tile).is_empty()), "wall covers tile at {px}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rect_bounds() {
+        let shape = ObjectShape::Rect { width: 64.0, height: 32.0 };
+
+        let (min, max) = shape_bounds(&shape, 10.0, 20.0, 0.0).unwrap();
+
+        assert_eq!(min, Vec2::new(10.0, 20.0));
+        assert_eq!(max, Vec2::new(74.0, 52.0));
+    }
+
+    #[test]
+    fn quarter_turn_rotates_around_origin() {
+        let shape = ObjectShape::Rect { width: 64.0, height: 32.0 };
+
+        // Clockwise on screen with Y down: the width now points down
+        let (min, max) = shape_bounds(&shape, 0.0, 0.0, 90.0).unwrap();
+
+        assert!(min.distance(Vec2::new(-32.0, 0.0)) < 1e-3);
+        assert!(max.distance(Vec2::new(0.0, 64.0)) < 1e-3);
+    }
+
+    #[test]
+    fn odd_rotation_is_skipped() {
+        let shape = ObjectShape::Rect { width: 64.0, height: 32.0 };
+
+        assert!(shape_bounds(&shape, 0.0, 0.0, 45.0).is_none());
+    }
+
+    #[test]
+    fn polygon_bounds() {
+        let shape = ObjectShape::Polygon { points: vec![(0.0, 0.0), (16.0, -8.0), (-4.0, 12.0)] };
+
+        let (min, max) = shape_bounds(&shape, 100.0, 100.0, 0.0).unwrap();
+
+        assert_eq!(min, Vec2::new(96.0, 92.0));
+        assert_eq!(max, Vec2::new(116.0, 112.0));
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::VecDeque;
+use bevy::prelude::*;
+use crate::{
+    core::{common::{Dying, MoveTo, PlayerMinion, Stats, Target}, navigation::FlowFieldGoal, targeting::Perception},
+    world::minnions::{minnion::{MinnionMode, ARRIVE_RADIUS}, unit::profile},
+};
+
+/// Spot a move-like command leads to
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Waypoint {
+    /// The unit's own spot, its formation slot
+    pub slot: Vec2,
+    /// Middle of the formation, for groups big enough to share a flow field
+    pub flow: Option<Vec2>,
+}
+
+/// Order given to a minnion
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum UnitCommand {
+    /// Walk there, ignoring enemies on the way
+    Move(Waypoint),
+    /// Walk there, fighting anything met on the way
+    AttackMove(Waypoint),
+    /// Chase and fight this one until it dies
+    Attack(Entity),
+    /// Stay put, only fight what comes in reach
+    Hold,
+    /// Walk back and forth between two points, fighting on the way
+    Patrol { from: Vec2, to: Vec2 },
+}
+
+impl UnitCommand {
+    /// Whether the unit fights under this order, whatever its `MinnionMode`
+    pub fn engages(&self) -> bool {
+        !matches!(self, UnitCommand::Move(_))
+    }
+
+    /// Where the unit ends up after this order, to chain queued orders from
+    pub fn destination(&self) -> Option<Vec2> {
+        match self {
+            UnitCommand::Move(waypoint) | UnitCommand::AttackMove(waypoint) => Some(waypoint.slot),
+            UnitCommand::Patrol { to, .. } => Some(*to),
+            UnitCommand::Attack(_) | UnitCommand::Hold => None,
+        }
+    }
+}
+
+/// Orders of a minnion, the first one is carried out. Shift-clicked orders are queued behind it.
+#[derive(Component, Default)]
+pub struct CommandQueue {
+    commands: VecDeque<UnitCommand>,
+    /// The current order already got its `MoveTo`/`Target`
+    started: bool,
+}
+
+impl CommandQueue {
+    /// Drops every order and starts on `command` right away
+    pub fn replace(&mut self, command: UnitCommand) {
+        self.commands.clear();
+        self.commands.push_back(command);
+        self.started = false;
+    }
+
+    /// Carries out `command` after everything already queued
+    pub fn push(&mut self, command: UnitCommand) {
+        self.commands.push_back(command);
+    }
+
+    pub fn current(&self) -> Option<&UnitCommand> {
+        self.commands.front()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &UnitCommand> {
+        self.commands.iter()
+    }
+
+    /// Whether the current order makes the unit fight
+    pub fn engages(&self) -> bool {
+        self.current().is_some_and(UnitCommand::engages)
+    }
+
+    /// Whether the unit has to stay where it is
+    pub fn holds(&self) -> bool {
+        self.current() == Some(&UnitCommand::Hold)
+    }
+
+    /// Moves on to the next order. A patrol never ends, it turns around instead.
+    fn finish(&mut self) {
+        if let Some(UnitCommand::Patrol { from, to }) = self.commands.pop_front() {
+            self.commands.push_front(UnitCommand::Patrol { from: to, to: from });
+        }
+        self.started = false;
+    }
+}
+
+/// Everything `run_commands` reads from a minnion
+type Commanded<'a> = (
+    Entity,
+    &'a mut CommandQueue,
+    &'a mut Perception,
+    &'a Transform,
+    &'a MinnionMode,
+    Option<&'a MoveTo>,
+    Option<&'a Target>,
+    &'a PlayerMinion,
+);
+
+/// Turns the current order of every minnion into `MoveTo`/`Target`, and moves on once it's done.
+/// Units that never fight, like healers, skip attack orders and don't look for targets.
+pub fn run_commands(
+    mut commands: Commands,
+    mut minnions: Query<Commanded, Without<Dying>>,
+    alive: Query<(), (With<Stats>, Without<Dying>)>,
+) {
+    for (mn, mut queue, mut perception, transform, mode, maybe_mt, maybe_target, minnion) in minnions.iter_mut() {
+        let position = transform.translation.truncate();
+        let fighter = profile(minnion.0).fights();
+        let aggressive = fighter && *mode == MinnionMode::Aggresiv;
+
+        let Some(command) = queue.current().copied() else {
+            perception.active = aggressive;
+            continue;
+        };
+        // A given target isn't swapped for whatever is nearest
+        perception.active = match command {
+            UnitCommand::Attack(_) => false,
+            command => aggressive || (fighter && command.engages()),
+        };
+
+        let fighting = maybe_target.is_some() && command.engages();
+        let done = match command {
+            UnitCommand::Move(waypoint) | UnitCommand::AttackMove(waypoint) => {
+                !fighting && position.distance(waypoint.slot) <= ARRIVE_RADIUS
+            }
+            UnitCommand::Patrol { to, .. } => !fighting && position.distance(to) <= ARRIVE_RADIUS,
+            UnitCommand::Attack(enemy) => !fighter || !alive.contains(enemy),
+            UnitCommand::Hold => false,
+        };
+        if done {
+            queue.finish();
+            if queue.current().is_none() {
+                commands.entity(mn).remove::<(MoveTo, FlowFieldGoal)>();
+            }
+            continue;
+        }
+
+        let mut entity = commands.entity(mn);
+        match command {
+            // Fights on the way, walks on once the target is gone
+            UnitCommand::AttackMove(_) | UnitCommand::Patrol { .. } if fighting => {
+                entity.remove::<(MoveTo, FlowFieldGoal)>();
+            }
+            UnitCommand::Move(waypoint) | UnitCommand::AttackMove(waypoint) => {
+                if !queue.started || maybe_mt.is_none() {
+                    entity.insert(MoveTo { loc: waypoint.slot.extend(0.0) });
+                    match waypoint.flow {
+                        Some(center) => entity.insert(FlowFieldGoal(center)),
+                        None => entity.remove::<FlowFieldGoal>(),
+                    };
+                }
+            }
+            UnitCommand::Patrol { to, .. } => {
+                if !queue.started || maybe_mt.is_none() {
+                    entity.insert(MoveTo { loc: to.extend(0.0) }).remove::<FlowFieldGoal>();
+                }
+            }
+            UnitCommand::Attack(enemy) => {
+                if maybe_target.is_none_or(|target| target.target != enemy) {
+                    entity.insert(Target { target: enemy });
+                }
+                entity.remove::<(MoveTo, FlowFieldGoal)>();
+            }
+            UnitCommand::Hold => {
+                entity.remove::<(MoveTo, FlowFieldGoal)>();
+            }
+        }
+        queue.started = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn waypoint(x: f32) -> Waypoint {
+        Waypoint { slot: Vec2::new(x, 0.0), flow: None }
+    }
+
+    #[test]
+    fn queued_orders_run_one_after_another() {
+        let mut queue = CommandQueue::default();
+        queue.replace(UnitCommand::Move(waypoint(10.0)));
+        queue.push(UnitCommand::AttackMove(waypoint(20.0)));
+        queue.push(UnitCommand::Hold);
+
+        assert!(!queue.engages());
+        queue.finish();
+        assert_eq!(queue.current(), Some(&UnitCommand::AttackMove(waypoint(20.0))));
+        assert!(queue.engages());
+        queue.finish();
+        assert!(queue.holds());
+
+        queue.replace(UnitCommand::Move(waypoint(30.0)));
+        assert_eq!(queue.iter().count(), 1);
+    }
+
+    #[test]
+    fn patrol_turns_around() {
+        let mut queue = CommandQueue::default();
+        let (a, b) = (Vec2::ZERO, Vec2::new(100.0, 0.0));
+        queue.replace(UnitCommand::Patrol { from: a, to: b });
+
+        queue.finish();
+
+        assert_eq!(queue.current(), Some(&UnitCommand::Patrol { from: b, to: a }));
+    }
+}
@@ -1,16 +1,147 @@
 use bevy::{prelude::*, window::PrimaryWindow};
-use crate::{core::common::MoveTo, world::minnions::minnion::{Minnion, MinnionMode}};
+use crate::{
+    core::{common::{Collider, Dying, Faction, FactionTable, PlayerMinion}, hitbox::Hurtbox},
+    world::minnions::{
+        command::{CommandQueue, UnitCommand, Waypoint},
+        formation::{assign_slots, place, Formation},
+        minnion::{Minnion, MinnionMode},
+        stance::Stance,
+    },
+};
 
+/// Move orders to at least this many minnions share one flow field instead of planning a path each
+const FLOW_FIELD_GROUP: usize = 8;
+/// Drags shorter than this are clicks. A right-click formation faces away from the group.
+const MIN_DRAG: f32 = 20.0;
+/// Two clicks on the same minnion within this many seconds are a double-click
+const DOUBLE_CLICK: f32 = 0.3;
+/// Keys of the control groups, `Ctrl` + key assigns the selection, the key alone recalls it
+const GROUP_KEYS: [KeyCode; 9] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+/// Formation used for move orders, cycled with `G`. Right-click moves the selection,
+/// right-drag sets which way the formation faces. Ctrl makes it an attack-move, Alt a patrol
+/// and Shift queues the order behind the ones already given.
+#[derive(Resource)]
+pub struct FormationOrder {
+    pub formation: Formation,
+    /// Distance between neighbouring slots
+    pub spacing: f32,
+    /// Where the current right-drag started
+    drag_start: Option<Vec2>,
+}
+
+impl Default for FormationOrder {
+    fn default() -> Self {
+        Self { formation: Formation::default(), spacing: 60.0, drag_start: None }
+    }
+}
+
+/// Left-drag box, in screen coordinates
 #[derive(Resource, Default)]
 pub struct SelectionBox {
     pub start: Option<Vec2>,
     pub end: Option<Vec2>,
     pub entity: Option<Entity>, 
+    /// Minnion clicked last and when, to spot double-clicks
+    last_click: Option<(Entity, f32)>,
+}
+
+/// Minnions saved under the number keys
+#[derive(Resource, Default)]
+pub struct ControlGroups {
+    groups: [Vec<Entity>; 9],
 }
 
 #[derive(Component)]
 struct Selected;
 
+/// How a click, drag or recalled group changes the selection. Shift adds to it, Ctrl removes from it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum SelectMode {
+    Replace,
+    Add,
+    Remove,
+}
+
+impl SelectMode {
+    fn from_keys(keyboard: &ButtonInput<KeyCode>) -> Self {
+        if keyboard.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+            SelectMode::Add
+        } else if keyboard.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+            SelectMode::Remove
+        } else {
+            SelectMode::Replace
+        }
+    }
+
+    /// Whether a minnion is selected afterwards, given whether it is now and whether it was picked
+    fn apply(self, selected: bool, picked: bool) -> bool {
+        match self {
+            SelectMode::Replace => picked,
+            SelectMode::Add => selected || picked,
+            SelectMode::Remove => selected && !picked,
+        }
+    }
+}
+
+/// Whether clicking `unit` at `now` is the second click of a double-click
+fn is_double_click(last: Option<(Entity, f32)>, unit: Entity, now: f32) -> bool {
+    last.is_some_and(|(clicked, at)| clicked == unit && now - at <= DOUBLE_CLICK)
+}
+
+/// Filter for minnions in the current selection
+type SelectedMinnion = (With<Minnion>, With<Selected>);
+
+/// Filter for minnions still in the fight
+type LivingMinnion = (With<Minnion>, Without<Dying>);
+
+/// Selected minnions receiving an order
+type Ordered<'a> = (&'a Transform, &'a mut CommandQueue);
+
+/// Kind of order a right-click gives, picked by the held modifier
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum OrderKind {
+    Move,
+    AttackMove,
+    Patrol,
+}
+
+impl OrderKind {
+    fn from_keys(keyboard: &ButtonInput<KeyCode>) -> Self {
+        if keyboard.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+            OrderKind::AttackMove
+        } else if keyboard.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]) {
+            OrderKind::Patrol
+        } else {
+            OrderKind::Move
+        }
+    }
+
+    /// The order for one unit heading from `from` to its `waypoint`
+    fn command(self, from: Vec2, waypoint: Waypoint) -> UnitCommand {
+        match self {
+            OrderKind::Move => UnitCommand::Move(waypoint),
+            OrderKind::AttackMove => UnitCommand::AttackMove(waypoint),
+            OrderKind::Patrol => UnitCommand::Patrol { from, to: waypoint.slot },
+        }
+    }
+}
+
+/// Whether Shift is held, queueing the order instead of replacing the current ones
+fn queueing(keyboard: &ButtonInput<KeyCode>) -> bool {
+    keyboard.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight])
+}
+
 #[derive(Component)]
 pub struct SelectionOutline;
 
@@ -19,7 +150,9 @@ pub struct ControlMinnionsPlugin ;
 impl Plugin for ControlMinnionsPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(SelectionBox::default())
-        .add_systems(Update, (start_drag_system, update_drag_system, end_drag_system, command_selected_minnions, change_selected_mode));
+        .init_resource::<FormationOrder>()
+        .init_resource::<ControlGroups>()
+        .add_systems(Update, (start_drag_system, update_drag_system, end_drag_system, control_groups, sync_outlines, command_selected_minnions, change_selected_mode, set_stance, cycle_formation, formation_drag, hold_position, show_orders));
     }
 }
 
@@ -28,7 +161,6 @@ fn start_drag_system(
     mut selection: ResMut<SelectionBox>,
     mouse: Res<ButtonInput<MouseButton>>,
     windows: Query<&Window, With<PrimaryWindow>>,
-    q_camera: Query<(&Camera, &GlobalTransform), With<Camera2d>>
 ) {
     if mouse.just_pressed(MouseButton::Left) {
         // Get window
@@ -39,35 +171,26 @@ fn start_drag_system(
 
         // Get sursor pos
         if let Some(screen_pos) = window.cursor_position() {
-            // Get camera and camera transform
-            if let Ok((camera, cam_tf)) = q_camera.single() {
-                // Convert screen pos on world pos
-                if let Ok(world_pos) = camera.viewport_to_world(cam_tf, screen_pos)
-                                               .map(|ray| ray.origin.truncate()) {
-                    // Set start 
-                    selection.start = Some(world_pos);
-                    selection.end = Some(world_pos); 
-
-
-                    let rect = commands
-                        .spawn((
-                            // white semi-transparent 1×1 sprite, scaled later
-                            Sprite {
-                                color: Color::linear_rgba(0.3, 0.5, 1.0, 0.2),
-                                custom_size: Some(Vec2::ONE),
-                                ..Default::default()
-                            },
-                            Transform {
-                                translation: world_pos.extend(0.0),
-                                ..Default::default()
-                            },
-                            GlobalTransform::default()
-                        ))
-                        .id();
-
-                    selection.entity = Some(rect);
-                }
-            }
+            // Set start 
+            selection.start = Some(screen_pos);
+            selection.end = Some(screen_pos); 
+
+            // Overlay drawn above the world, resized while dragging
+            let rect = commands
+                .spawn((
+                    Node {
+                        position_type: PositionType::Absolute,
+                        left: Val::Px(screen_pos.x),
+                        top: Val::Px(screen_pos.y),
+                        border: UiRect::all(Val::Px(1.0)),
+                        ..default()
+                    },
+                    BackgroundColor(Color::linear_rgba(0.3, 0.5, 1.0, 0.2)),
+                    BorderColor(Color::linear_rgba(0.3, 0.5, 1.0, 0.8)),
+                ))
+                .id();
+
+            selection.entity = Some(rect);
         }
     }
 }
@@ -77,8 +200,7 @@ fn update_drag_system(
     mut selection: ResMut<SelectionBox>,
     mouse: Res<ButtonInput<MouseButton>>,
     windows: Query<&Window, With<PrimaryWindow>>,
-    q_camera: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
-    mut query: Query<(&mut Transform, &mut Sprite)>
+    mut query: Query<&mut Node>
 ) {
     if mouse.pressed(MouseButton::Left) {
         // Check if rect exists
@@ -91,76 +213,89 @@ fn update_drag_system(
             };
             // Get cursor pos if it is in window
             if let Some(screen_pos) = window.cursor_position() {
-                // Get camera and camera tranform
-                if let Ok((camera, camera_tf)) = q_camera.single() {
-                    // Get camera global pos
-                    if let Ok(world_pos) = camera
-                        .viewport_to_world(camera_tf, screen_pos)
-                        .map(|r| r.origin.truncate())
-                    {
-                        selection.end = Some(world_pos);
-
-                        // Needed informations.
-                        
-                        // Lower left corner of rectangle
-                        let min = start.min(world_pos);
-                        // Upper right corner of rectangle
-                        let max = start.max(world_pos);
-                        // Size of rect
-                        let size = max - min;
-                        // Center of rect
-                        let center = (min + max) / 2.0;
-                        // Update rect sprite
-                        if let Ok((mut transform, mut sprite)) = query.get_mut(react_entity) {
-                            transform.translation = center.extend(0.0);
-                            sprite.custom_size = Some(size);
-                        }
-                    }
+                selection.end = Some(screen_pos);
+
+                // Update rect node
+                let rect = Rect::from_corners(start, screen_pos);
+                if let Ok(mut node) = query.get_mut(react_entity) {
+                    node.left = Val::Px(rect.min.x);
+                    node.top = Val::Px(rect.min.y);
+                    node.width = Val::Px(rect.width());
+                    node.height = Val::Px(rect.height());
                 }
             }
         }      
     }
 }
 
+/// Screen-space bounds of a collider at `position`
+fn screen_bounds(camera: &Camera, camera_tf: &GlobalTransform, position: Vec3, radius: f32) -> Option<Rect> {
+    let corner = Vec3::new(radius, radius, 0.0);
+    let a = camera.world_to_viewport(camera_tf, position - corner).ok()?;
+    let b = camera.world_to_viewport(camera_tf, position + corner).ok()?;
+    Some(Rect::from_corners(a, b))
+}
+
+/// Whether the drag box `area` catches a unit with these screen bounds, touching it is enough
+fn box_selects(area: Rect, bounds: Rect) -> bool {
+    area.min.cmple(bounds.max).all() && bounds.min.cmple(area.max).all()
+}
+
+/// Selects the minnions touched by the box, or the one clicked. Double-clicking a minnion selects
+/// every minnion of its type on screen.
+/// Everything is tested in screen space, against the projected colliders.
+#[allow(clippy::too_many_arguments)]
 fn end_drag_system(
     mouse: Res<ButtonInput<MouseButton>>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
     mut selection: ResMut<SelectionBox>,
     mut commands: Commands,
-    q_minnions: Query<(Entity, &Transform, &Children), With<Minnion>>,
-    q_outlines: Query<Entity, With<SelectionOutline>>,
+    q_minnions: Query<(Entity, &GlobalTransform, &Collider, Has<Selected>), With<Minnion>>,
+    q_types: Query<&PlayerMinion>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
 ) {
     if mouse.just_released(MouseButton::Left) {
-        if let (Some(start), Some(end)) = (selection.start, selection.end) {
-            let min_x = start.x.min(end.x);
-            let max_x = start.x.max(end.x);
-            let min_y = start.y.min(end.y);
-            let max_y = start.y.max(end.y);
-
-            for (mn, mn_tf, children) in q_minnions.iter() {
-                let tr = mn_tf.translation;
-                if tr.x >= min_x && tr.x <= max_x && tr.y >= min_y && tr.y <= max_y {
-                    commands.entity(mn).insert(Selected);
-                    commands.entity(mn).with_children(|parent| {
-                        parent.spawn((
-                            Sprite {
-                                color: Color::WHITE,
-                                custom_size: Some(Vec2::new(30.0, 30.0)), 
-                                ..default()
-                            },
-                            Transform::from_xyz(0.0, 0.0, -0.1), 
-                            SelectionOutline
-                        ));
-                    });
-                } else {
-                    commands.entity(mn).remove::<Selected>();
+        if let (Some(start), Some(end), Ok((camera, camera_tf))) = (selection.start, selection.end, q_camera.single()) {
+            let on_screen: Vec<(Entity, Rect)> = q_minnions
+                .iter()
+                .filter_map(|(mn, tf, collider, _)| Some((mn, screen_bounds(camera, camera_tf, tf.translation(), collider.radius)?)))
+                .collect();
 
-                   for child in children {
-                        if q_outlines.get(*child).is_ok() {
-                            commands.entity(*child).despawn();
-                        }
+            let picked: Vec<Entity> = if start.distance(end) < MIN_DRAG {
+                let clicked = on_screen.iter().find(|(_, bounds)| bounds.contains(end)).map(|(mn, _)| *mn);
+                let now = time.elapsed_secs();
+
+                match clicked {
+                    Some(mn) if is_double_click(selection.last_click, mn, now) => {
+                        selection.last_click = None;
+                        let screen = camera.logical_viewport_rect().unwrap_or(Rect::EMPTY);
+                        let kind = q_types.get(mn).ok().map(|minnion| minnion.0);
+                        on_screen
+                            .iter()
+                            .filter(|(other, bounds)| {
+                                box_selects(screen, *bounds) && q_types.get(*other).ok().map(|minnion| minnion.0) == kind
+                            })
+                            .map(|(mn, _)| *mn)
+                            .collect()
+                    }
+                    Some(mn) => {
+                        selection.last_click = Some((mn, now));
+                        vec![mn]
                     }
+                    None => Vec::new(),
                 }
-            }
+            } else {
+                let area = Rect::from_corners(start, end);
+                on_screen
+                    .iter()
+                    .filter(|(_, bounds)| box_selects(area, *bounds))
+                    .map(|(mn, _)| *mn)
+                    .collect()
+            };
+
+            let minnions = q_minnions.iter().map(|(mn, _, _, selected)| (mn, selected));
+            select(&mut commands, minnions, &picked, SelectMode::from_keys(&keyboard));
         }
 
         if let Some(rect) = selection.entity.take() {
@@ -172,12 +307,226 @@ fn end_drag_system(
     }
 }
 
+/// Updates `Selected` on the `minnions`, given with whether they're selected now
+fn select(
+    commands: &mut Commands,
+    minnions: impl Iterator<Item = (Entity, bool)>,
+    picked: &[Entity],
+    mode: SelectMode,
+) {
+    for (mn, selected) in minnions {
+        match (selected, mode.apply(selected, picked.contains(&mn))) {
+            (false, true) => {
+                commands.entity(mn).insert(Selected);
+            }
+            (true, false) => {
+                commands.entity(mn).remove::<Selected>();
+            }
+            _ => {}
+        }
+    }
+}
 
-fn command_selected_minnions (
+/// `Ctrl` + number saves the selection as a control group, the number alone selects it again
+fn control_groups(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut groups: ResMut<ControlGroups>,
+    mut commands: Commands,
+    q_minnions: Query<(Entity, Has<Selected>), LivingMinnion>,
+) {
+    let Some(group) = GROUP_KEYS.iter().position(|key| keyboard.just_pressed(*key)) else {
+        return;
+    };
+
+    if keyboard.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        groups.groups[group] = q_minnions.iter().filter(|(_, selected)| *selected).map(|(mn, _)| mn).collect();
+        info!("Control group {}: {} minnions", group + 1, groups.groups[group].len());
+        return;
+    }
+
+    // Fallen minnions leave their group
+    groups.groups[group].retain(|mn| q_minnions.contains(*mn));
+    if groups.groups[group].is_empty() {
+        return;
+    }
+    let mode = match SelectMode::from_keys(&keyboard) {
+        SelectMode::Add => SelectMode::Add,
+        _ => SelectMode::Replace,
+    };
+    select(&mut commands, q_minnions.iter(), &groups.groups[group], mode);
+}
+
+/// Gives selected minnions an outline and takes it away once they're deselected
+fn sync_outlines(
     mut commands: Commands,
+    q_added: Query<Entity, Added<Selected>>,
+    mut removed: RemovedComponents<Selected>,
+    q_children: Query<&Children>,
+    q_outlines: Query<Entity, With<SelectionOutline>>,
+) {
+    let outline = |mn: Entity| {
+        q_children
+            .get(mn)
+            .ok()
+            .and_then(|children| children.iter().find(|child| q_outlines.contains(*child)))
+    };
+
+    for mn in q_added.iter() {
+        if outline(mn).is_none() {
+            commands.entity(mn).with_children(|parent| {
+                parent.spawn((
+                    Sprite {
+                        color: Color::WHITE,
+                        custom_size: Some(Vec2::new(30.0, 30.0)), 
+                        ..default()
+                    },
+                    Transform::from_xyz(0.0, 0.0, -0.1), 
+                    SelectionOutline
+                ));
+            });
+        }
+    }
+    for mn in removed.read() {
+        if let Some(outline) = outline(mn) {
+            commands.entity(outline).despawn();
+        }
+    }
+}
+
+/// Cursor position in world space
+fn cursor_world_pos(
+    windows: &Query<&Window, With<PrimaryWindow>>,
+    q_camera: &Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+) -> Option<Vec2> {
+    let screen_pos = windows.single().ok()?.cursor_position()?;
+    let (camera, camera_tf) = q_camera.single().ok()?;
+    camera.viewport_to_world_2d(camera_tf, screen_pos).ok()
+}
+
+/// Orders the minnions to their own slot of the current formation around `center`.
+/// Without a `facing` the formation faces away from where the group stands now.
+/// Queued orders start where the previous ones end.
+fn order_formation(
+    order: &FormationOrder,
+    mut minnions: Vec<(Vec2, Mut<CommandQueue>)>,
+    center: Vec2,
+    facing: Option<Vec2>,
+    kind: OrderKind,
+    queued: bool,
+) {
+    if minnions.is_empty() {
+        return;
+    }
+
+    let positions: Vec<Vec2> = minnions
+        .iter()
+        .map(|(pos, queue)| match queued {
+            true => queue.iter().filter_map(UnitCommand::destination).last().unwrap_or(*pos),
+            false => *pos,
+        })
+        .collect();
+    let centroid = positions.iter().sum::<Vec2>() / positions.len() as f32;
+    let facing = facing.unwrap_or(center - centroid);
+
+    let slots = place(&order.formation.slots(minnions.len(), order.spacing), center, facing);
+    let flow = (minnions.len() >= FLOW_FIELD_GROUP).then_some(center);
+
+    for (((_, queue), from), slot) in minnions.iter_mut().zip(&positions).zip(assign_slots(&positions, &slots)) {
+        let command = kind.command(*from, Waypoint { slot: slots[slot], flow });
+        if queued {
+            queue.push(command);
+        } else {
+            queue.replace(command);
+        }
+    }
+}
+
+/// Hostile unit whose hurtbox is under the cursor
+fn enemy_at(
+    cursor: Vec2,
+    enemies: &Query<(Entity, &Transform, &Hurtbox, &Faction), Without<Dying>>,
+    factions: &FactionTable,
+) -> Option<Entity> {
+    enemies
+        .iter()
+        .find(|(_, tf, hurtbox, faction)| {
+            factions.is_hostile(Faction::Player, **faction)
+                && Rect::from_center_half_size(tf.translation.truncate() + hurtbox.offset, hurtbox.half_size).contains(cursor)
+        })
+        .map(|(enemy, ..)| enemy)
+}
+
+fn cycle_formation(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut order: ResMut<FormationOrder>,
+) {
+    if keyboard.just_pressed(KeyCode::KeyG) {
+        order.formation = order.formation.next();
+        info!("Formation: {:?}", order.formation);
+    }
+}
+
+/// Right-click orders the selection into formation, dragging turns it. The slots are previewed while dragging.
+/// Clicking an enemy attacks it instead.
+#[allow(clippy::too_many_arguments)]
+fn formation_drag(
+    mouse: Res<ButtonInput<MouseButton>>,
     keyboard: Res<ButtonInput<KeyCode>>,
+    mut order: ResMut<FormationOrder>,
     windows: Query<&Window, With<PrimaryWindow>>,
-    q_minnions: Query<Entity, (With<Minnion>, With<Selected>)>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    mut q_minnions: Query<Ordered, SelectedMinnion>,
+    q_enemies: Query<(Entity, &Transform, &Hurtbox, &Faction), Without<Dying>>,
+    factions: Res<FactionTable>,
+    mut gizmos: Gizmos,
+) {
+    let Some(cursor) = cursor_world_pos(&windows, &q_camera) else {
+        return;
+    };
+
+    if mouse.just_pressed(MouseButton::Right) {
+        order.drag_start = Some(cursor);
+    }
+    let Some(start) = order.drag_start else {
+        return;
+    };
+
+    let drag = cursor - start;
+    let facing = (drag.length() >= MIN_DRAG).then_some(drag);
+
+    if let Some(facing) = facing.filter(|_| mouse.pressed(MouseButton::Right)) {
+        let count = q_minnions.iter().len();
+        for slot in place(&order.formation.slots(count, order.spacing), start, facing) {
+            gizmos.circle_2d(slot, 12.0, Color::srgba(0.3, 0.5, 1.0, 0.6));
+        }
+        gizmos.arrow_2d(start, start + facing.normalize() * order.spacing, Color::srgba(0.3, 0.5, 1.0, 0.6));
+    }
+
+    if mouse.just_released(MouseButton::Right) {
+        order.drag_start = None;
+        let queued = queueing(&keyboard);
+
+        if let Some(enemy) = enemy_at(start, &q_enemies, &factions).filter(|_| facing.is_none()) {
+            for (_, mut queue) in q_minnions.iter_mut() {
+                if queued {
+                    queue.push(UnitCommand::Attack(enemy));
+                } else {
+                    queue.replace(UnitCommand::Attack(enemy));
+                }
+            }
+            return;
+        }
+
+        let minnions = q_minnions.iter_mut().map(|(tf, queue)| (tf.translation.truncate(), queue)).collect();
+        order_formation(&order, minnions, start, facing, OrderKind::from_keys(&keyboard), queued);
+    }
+}
+
+fn command_selected_minnions (
+    keyboard: Res<ButtonInput<KeyCode>>,
+    order: Res<FormationOrder>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut q_minnions: Query<Ordered, SelectedMinnion>,
     q_camera: Query<(&Camera, &GlobalTransform), With<Camera2d>>
 ) {
     if keyboard.just_pressed(KeyCode::KeyM) {
@@ -193,9 +542,8 @@ fn command_selected_minnions (
                         .viewport_to_world(camera_tf, screen_pos)
                         .map(|r| r.origin.truncate())
                     {
-                       for mn in q_minnions {
-                            commands.entity(mn).insert(MoveTo { loc: Vec3 { x: world_pos.x, y: world_pos.y, z: 0. } });
-                        }
+                       let minnions = q_minnions.iter_mut().map(|(tf, queue)| (tf.translation.truncate(), queue)).collect();
+                       order_formation(&order, minnions, world_pos, None, OrderKind::Move, queueing(&keyboard));
                     }
                 }
             }
@@ -222,4 +570,85 @@ fn change_selected_mode(
             commands.entity(mn).insert(MinnionMode::Passiv);
         }
     }
-}
\ No newline at end of file
+}
+
+/// `H` makes the selection hold its position
+fn hold_position(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut q_minnions: Query<&mut CommandQueue, SelectedMinnion>,
+) {
+    if keyboard.just_pressed(KeyCode::KeyH) {
+        for mut queue in q_minnions.iter_mut() {
+            queue.replace(UnitCommand::Hold);
+        }
+    }
+}
+
+/// Draws the path of queued orders of the selected minnions
+fn show_orders(
+    q_minnions: Query<(&Transform, &CommandQueue), SelectedMinnion>,
+    mut gizmos: Gizmos,
+) {
+    let color = Color::srgba(0.3, 1.0, 0.5, 0.5);
+    for (tf, queue) in q_minnions.iter() {
+        let mut from = tf.translation.truncate();
+        for to in queue.iter().filter_map(UnitCommand::destination) {
+            gizmos.line_2d(from, to, color);
+            gizmos.circle_2d(to, 4.0, color);
+            from = to;
+        }
+    }
+}
+
+/// Stance of the selection once it runs out of orders: `C` follows the player,
+/// `X` guards where the minnions stand now and `Z` lets them stay wherever they end up
+fn set_stance(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut q_minnions: Query<(&Transform, &mut Stance), SelectedMinnion>,
+) {
+    for (tf, mut stance) in q_minnions.iter_mut() {
+        if keyboard.just_pressed(KeyCode::KeyC) {
+            *stance = Stance::Follow;
+        } else if keyboard.just_pressed(KeyCode::KeyX) {
+            *stance = Stance::Guard { anchor: tf.translation.truncate() };
+        } else if keyboard.just_pressed(KeyCode::KeyZ) {
+            *stance = Stance::Free;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modifiers_add_to_or_remove_from_the_selection() {
+        assert!(SelectMode::Replace.apply(false, true));
+        assert!(!SelectMode::Replace.apply(true, false));
+        assert!(SelectMode::Add.apply(true, false));
+        assert!(SelectMode::Add.apply(false, true));
+        assert!(!SelectMode::Remove.apply(true, true));
+        assert!(SelectMode::Remove.apply(true, false));
+        assert!(!SelectMode::Remove.apply(false, false));
+    }
+
+    #[test]
+    fn double_click_needs_the_same_minnion_quickly() {
+        let (a, b) = (Entity::from_raw(1), Entity::from_raw(2));
+
+        assert!(is_double_click(Some((a, 1.0)), a, 1.2));
+        assert!(!is_double_click(Some((a, 1.0)), a, 1.5));
+        assert!(!is_double_click(Some((a, 1.0)), b, 1.1));
+        assert!(!is_double_click(None, a, 1.0));
+    }
+
+    #[test]
+    fn box_catches_units_it_touches() {
+        let area = Rect::new(0.0, 0.0, 100.0, 100.0);
+
+        // Centre outside, but the collider reaches into the box
+        assert!(box_selects(area, Rect::from_center_half_size(Vec2::new(110.0, 50.0), Vec2::splat(20.0))));
+        assert!(box_selects(area, Rect::from_center_half_size(Vec2::new(50.0, 50.0), Vec2::splat(20.0))));
+        assert!(!box_selects(area, Rect::from_center_half_size(Vec2::new(130.0, 50.0), Vec2::splat(20.0))));
+    }
+}
@@ -0,0 +1,146 @@
+use bevy::prelude::*;
+
+/// Shape a group of minnions takes when ordered to move
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Formation {
+    /// One wide rank
+    #[default]
+    Line,
+    /// Two abreast, one rank behind the other
+    Column,
+    /// Arrow with a single unit at the tip
+    Wedge,
+    /// Square block
+    Box,
+}
+
+impl Formation {
+    /// Next formation when cycling through them
+    pub fn next(self) -> Self {
+        match self {
+            Formation::Line => Formation::Column,
+            Formation::Column => Formation::Wedge,
+            Formation::Wedge => Formation::Box,
+            Formation::Box => Formation::Line,
+        }
+    }
+
+    /// Positions of `count` units relative to the formation center, facing +X
+    pub fn slots(self, count: usize, spacing: f32) -> Vec<Vec2> {
+        match self {
+            Formation::Line => ranks(count, count.max(1), spacing),
+            Formation::Column => ranks(count, 2, spacing),
+            Formation::Box => ranks(count, (count as f32).sqrt().ceil() as usize, spacing),
+            Formation::Wedge => {
+                // Row r holds r + 1 units, the last one whatever is left
+                let mut slots = Vec::with_capacity(count);
+                let mut row = 0;
+                while slots.len() < count {
+                    let in_row = (row + 1).min(count - slots.len());
+                    slots.extend(rank(row, in_row, spacing));
+                    row += 1;
+                }
+                centered(slots)
+            }
+        }
+    }
+}
+
+/// Slots of `count` units in ranks of `width`, front rank first
+fn ranks(count: usize, width: usize, spacing: f32) -> Vec<Vec2> {
+    let slots = (0..count.div_ceil(width))
+        .flat_map(|row| rank(row, width.min(count - row * width), spacing))
+        .collect();
+    centered(slots)
+}
+
+/// `count` units side by side, `row` ranks behind the front
+fn rank(row: usize, count: usize, spacing: f32) -> impl Iterator<Item = Vec2> {
+    (0..count).map(move |i| Vec2::new(-(row as f32), i as f32 - (count - 1) as f32 / 2.0) * spacing)
+}
+
+/// Moves the slots so the middle of the formation sits at the origin
+fn centered(slots: Vec<Vec2>) -> Vec<Vec2> {
+    let (min, max) = slots
+        .iter()
+        .fold((Vec2::MAX, Vec2::MIN), |(min, max), slot| (min.min(*slot), max.max(*slot)));
+    let middle = (min + max) / 2.0;
+    slots.into_iter().map(|slot| slot - middle).collect()
+}
+
+/// World positions of the slots around `center`, with the front facing `facing`
+pub fn place(slots: &[Vec2], center: Vec2, facing: Vec2) -> Vec<Vec2> {
+    let facing = facing.normalize_or(Vec2::X);
+    slots.iter().map(|slot| center + facing.rotate(*slot)).collect()
+}
+
+/// Gives every unit a slot, closest pairs first so units don't cross the whole group.
+/// Returns the slot index for each unit, there have to be at least as many slots as units.
+pub fn assign_slots(units: &[Vec2], slots: &[Vec2]) -> Vec<usize> {
+    let mut pairs: Vec<(f32, usize, usize)> = units
+        .iter()
+        .enumerate()
+        .flat_map(|(u, unit)| slots.iter().enumerate().map(move |(s, slot)| (unit.distance_squared(*slot), u, s)))
+        .collect();
+    pairs.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut assigned = vec![usize::MAX; units.len()];
+    let mut taken = vec![false; slots.len()];
+    for (_, unit, slot) in pairs {
+        if assigned[unit] == usize::MAX && !taken[slot] {
+            assigned[unit] = slot;
+            taken[slot] = true;
+        }
+    }
+    assigned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_formation_has_a_slot_per_unit() {
+        for formation in [Formation::Line, Formation::Column, Formation::Wedge, Formation::Box] {
+            for count in [1, 2, 5, 9, 10] {
+                let slots = formation.slots(count, 40.0);
+                assert_eq!(slots.len(), count, "{formation:?} with {count}");
+                // Nobody shares a slot
+                for (i, a) in slots.iter().enumerate() {
+                    assert!(slots[i + 1..].iter().all(|b| a.distance(*b) >= 39.0), "{formation:?} with {count}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn shapes() {
+        // A line is one rank across the facing
+        assert!(Formation::Line.slots(5, 40.0).iter().all(|slot| slot.x == 0.0));
+        // A column is two wide
+        assert!(Formation::Column.slots(6, 40.0).iter().all(|slot| slot.y.abs() == 20.0));
+        // The wedge tip is alone at the front
+        let wedge = Formation::Wedge.slots(6, 40.0);
+        let front = wedge.iter().map(|slot| slot.x).fold(f32::MIN, f32::max);
+        assert_eq!(wedge.iter().filter(|slot| slot.x == front).count(), 1);
+        // A box of 9 is 3 by 3 around the center
+        assert!(Formation::Box.slots(9, 40.0).contains(&Vec2::ZERO));
+    }
+
+    #[test]
+    fn placing_turns_the_front_to_the_facing() {
+        let slots = [Vec2::new(40.0, 0.0)];
+
+        let placed = place(&slots, Vec2::new(100.0, 100.0), Vec2::new(0.0, 2.0));
+
+        assert!(placed[0].distance(Vec2::new(100.0, 140.0)) < 1e-3);
+    }
+
+    #[test]
+    fn units_take_the_nearest_free_slot() {
+        let units = [Vec2::new(0.0, 0.0), Vec2::new(100.0, 0.0)];
+        let slots = [Vec2::new(90.0, 0.0), Vec2::new(10.0, 0.0)];
+
+        assert_eq!(assign_slots(&units, &slots), vec![1, 0]);
+    }
+}
@@ -1,10 +1,37 @@
-use std::{ clone, time::Duration };
-use crate::{core::common::{Animation, AnimationIndices, AnimationSet, AnimationState, AttackEvent, Collider, HitReactionTimer, MoveTo, Player, Stats, Target}, world::enemy::Enemy};
-use bevy::{platform::collections::HashMap, prelude::*, state::commands, window::PrimaryWindow};
+use std::time::Duration;
+use crate::{
+    core::{
+        common::{
+            Animation, AnimationIndices, AnimationSet, AnimationState, Collider, DamageType, Defense, Dying, Faction, HitReactionTimer,
+            MinionType, MoveTo, PlayerMinion, Stats, StatusEffects, Target, layers,
+        },
+        hitbox::{start_swing, swinging, HitShape, HitboxFrames, Hitboxes, Hurtbox},
+        navigation::{FlowFieldGoal, FlowFields, NavGrid, Navigator},
+        projectile::{launch_projectile, ProjectileSpec},
+        steering::{arrive, steer_units, Steering},
+        targeting::{Perception, TargetPriority},
+    },
+    world::minnions::{
+        command::{run_commands, CommandQueue},
+        stance::{keep_stance, Stance},
+        unit::{most_wounded, profile, Veterancy},
+    },
+};
+use bevy::{platform::collections::HashMap, prelude::*};
+#[cfg(feature = "cheats")]
+use bevy::window::PrimaryWindow;
 
 pub struct MinnionsPlugin;
 
+/// Close enough to a `MoveTo` spot or a command's destination, formation slots are this precise
+pub(crate) const ARRIVE_RADIUS: f32 = 10.0;
+/// Group movers leave the flow field this close to their own spot
+const FLOW_HANDOFF: f32 = 200.0;
+/// Archers stop chasing once their target is this fraction of their range away
+const RANGED_HOLD: f32 = 0.9;
+
 #[derive(Component)]
+#[require(CommandQueue)]
 pub struct  Minnion;
 
 #[derive(Resource, Default)]
@@ -13,6 +40,7 @@ pub struct SelectionBox {
     pub end: Option<Vec2>,
 }
 
+/// Cooldown between swings, shots or heals, each one starts only once it's up
 #[derive(Component)]
 struct MinnionAttackTimer {
     timer: Timer,
@@ -23,6 +51,7 @@ struct MinnionAttackTimer {
 pub struct MinnionHealthBar;
 
 
+#[cfg(feature = "cheats")]
 #[derive(Resource)]
 struct MinnionSpawnTimer(Timer);
 
@@ -33,25 +62,49 @@ pub enum MinnionMode {
     Passiv
 }
 
+/// Whether a minnion fights, on its own when aggressive or because it was ordered to.
+/// Healers never do.
+fn fights(kind: MinionType, mode: &MinnionMode, queue: &CommandQueue) -> bool {
+    profile(kind).fights() && (*mode == MinnionMode::Aggresiv || queue.engages())
+}
+
+/// Arrow shot by archers, damage is the archer's attack
+fn arrow(damage: i32) -> ProjectileSpec {
+    ProjectileSpec {
+        speed: 500.0,
+        lifetime: 1.0,
+        pierce: 0,
+        radius: 8.0,
+        damage,
+        damage_type: DamageType::Physical,
+        effects: Vec::new(),
+        layer: layers::MINION,
+        mask: layers::ENEMY,
+        sprite_angle: 0.0,
+    }
+}
+
 impl Plugin for MinnionsPlugin {
     fn build(&self, app: &mut App) {
+        #[cfg(feature = "cheats")]
         app.insert_resource(MinnionSpawnTimer(Timer::from_seconds(0.125, TimerMode::Once)))
-        .add_systems(
+        .add_systems(Update, cheat_spawn_minnion);
+
+        app.add_systems(
             Update, 
-            (spawn_minnion, 
-                update_hp_bars, 
+            (update_hp_bars, 
                 hit_reaction, 
-                find_enemy_target, 
-                drop_target, 
-                move_minnions_tow_target, 
+                (run_commands, keep_stance, move_minnions_tow_target).chain().before(steer_units), 
                 change_animation_state,
-                attack
+                (attack, shoot_arrows, heal_allies).chain()
             ));
     }
 }
 
 
-fn spawn_minnion(
+/// Debug spawn of free soldiers at the cursor while `P` is held
+#[cfg(feature = "cheats")]
+fn cheat_spawn_minnion(
     mut commands: Commands,
     q_windows: Query<&Window, With<PrimaryWindow>>,
     q_camera: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
@@ -89,73 +142,121 @@ fn spawn_minnion(
         spawn_timer.0.reset();
 
         if let Ok(cursor_pos) = world_pos {
-            let idle_tex: Handle<Image> = asset_server.load("Entities/Soldier/Soldier/Soldier-Idle.png");
-            let walk_tex: Handle<Image> = asset_server.load("Entities/Soldier/Soldier/Soldier-Walk.png");
-            let atk_tex: Handle<Image> = asset_server.load("Entities/Soldier/Soldier/Soldier-Attack01.png");
-            let hurt_tex: Handle<Image> = asset_server.load("Entities/Soldier/Soldier/Soldier-Hurt.png");
-            
-            // Load textures and layouts
-            let anim_layout   = texture_atlas_layouts.add(TextureAtlasLayout::from_grid(UVec2::splat(100), 6, 1, None, None));
-            let hurt_layout   = texture_atlas_layouts.add(TextureAtlasLayout::from_grid(UVec2::splat(100), 3, 1, None, None));
+            spawn_minnion(&mut commands, &asset_server, &mut texture_atlas_layouts, MinionType::Soldier, cursor_pos);
+        }
+    }
+}
 
+/// Spawns a minnion of type `kind` standing at `position`. All types share the soldier art, tinted by type.
+pub fn spawn_minnion(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    texture_atlas_layouts: &mut Assets<TextureAtlasLayout>,
+    kind: MinionType,
+    position: Vec2,
+) -> Entity {
+    let base = profile(kind);
+
+    let idle_tex: Handle<Image> = asset_server.load("Entities/Soldier/Soldier/Soldier-Idle.png");
+    let walk_tex: Handle<Image> = asset_server.load("Entities/Soldier/Soldier/Soldier-Walk.png");
+    let atk_tex: Handle<Image> = asset_server.load("Entities/Soldier/Soldier/Soldier-Attack01.png");
+    let atk2_tex: Handle<Image> = asset_server.load("Entities/Soldier/Soldier/Soldier-Attack02.png");
+    let bow_tex: Handle<Image> = asset_server.load("Entities/Soldier/Soldier/Soldier-Attack03.png");
+    let hurt_tex: Handle<Image> = asset_server.load("Entities/Soldier/Soldier/Soldier-Hurt.png");
+    let death_tex: Handle<Image> = asset_server.load("Entities/Soldier/Soldier/Soldier-Death.png");
     
-            let anim_map = HashMap::from([
-                (AnimationState::Idle,   (idle_tex.clone(), anim_layout.clone(), AnimationIndices { first: 0, last: 5 })),
-                (AnimationState::Walk,   (walk_tex.clone(), anim_layout.clone(), AnimationIndices { first: 0, last: 5 })),
-                (AnimationState::Attack01, (atk_tex.clone(),  anim_layout.clone(), AnimationIndices { first: 0, last: 5 })),
-                (AnimationState::Hurt,   (hurt_tex.clone(), hurt_layout.clone(), AnimationIndices { first: 0, last: 2 })),
-            ]);
+    // Load textures and layouts
+    let anim_layout   = texture_atlas_layouts.add(TextureAtlasLayout::from_grid(UVec2::splat(100), 6, 1, None, None));
+    let hurt_layout   = texture_atlas_layouts.add(TextureAtlasLayout::from_grid(UVec2::splat(100), 3, 1, None, None));
+    let death_layout  = texture_atlas_layouts.add(TextureAtlasLayout::from_grid(UVec2::splat(100), 4, 1, None, None));
+    let bow_layout    = texture_atlas_layouts.add(TextureAtlasLayout::from_grid(UVec2::splat(100), 9, 1, None, None));
 
-            let animation_set = AnimationSet{ animations: anim_map };
+    
+    let anim_map = HashMap::from([
+        (AnimationState::Idle,   (idle_tex.clone(), anim_layout.clone(), AnimationIndices { first: 0, last: 5 })),
+        (AnimationState::Walk,   (walk_tex.clone(), anim_layout.clone(), AnimationIndices { first: 0, last: 5 })),
+        (AnimationState::Attack01, (atk_tex.clone(),  anim_layout.clone(), AnimationIndices { first: 0, last: 5 })),
+        (AnimationState::Attack02, (atk2_tex.clone(), anim_layout.clone(), AnimationIndices { first: 0, last: 5 })),
+        (AnimationState::Attack03, (bow_tex.clone(),  bow_layout.clone(), AnimationIndices { first: 0, last: 8 })),
+        (AnimationState::Hurt,   (hurt_tex.clone(), hurt_layout.clone(), AnimationIndices { first: 0, last: 2 })),
+        (AnimationState::Death,  (death_tex.clone(), death_layout.clone(), AnimationIndices { first: 0, last: 3 })),
+    ]);
+
+    let animation_set = AnimationSet{ animations: anim_map };
+
+    let mut hit_timer = HitReactionTimer {
+        timer: Timer::from_seconds(0.4, TimerMode::Once),
+    };
 
-            let mut hit_timer = HitReactionTimer {
-                timer: Timer::from_seconds(0.4, TimerMode::Once),
-            };
+    hit_timer.timer.tick(Duration::from_secs_f32(0.4));
 
-            hit_timer.timer.tick(Duration::from_secs_f32(0.4));
-
-            let animation = Animation {
-                    set: animation_set,
-                    state: AnimationState::Idle,
-                    timer: Timer::from_seconds(0.1, TimerMode::Repeating),
-                    last_state: None
-                };
-
-            // Spawn Minion
-            let minnion_ent = commands.spawn((
-                Sprite::from_atlas_image(
-                    idle_tex,
-                    TextureAtlas { layout: anim_layout, index: 0 }
-                ),
-                animation,
-                Transform::from_scale(Vec3::splat(4.0)).with_translation(Vec3 {
-                            x: cursor_pos.x,
-                            y: cursor_pos.y,
-                            z: 0.0,
-                        }),
-                Minnion,
-                Collider { radius: 22. },
-                Stats { hp:100, max_hp:100, attack:25 },
-                hit_timer,
-                MinnionMode::Neutral,
-                MinnionAttackTimer {
-                    timer: Timer::from_seconds(0.6, TimerMode::Repeating),
-                },
-            )).id();
-
-            let hp_bar = Sprite {
-                    color: Color::srgb(125.0, 0.0, 0.0),
-                    custom_size: Some(Vec2::new(100. / 4., 1.0)),
-                    ..default()
-                };
-                
-            commands.entity(minnion_ent)
-            .with_children(| parent | {
-                parent.spawn((hp_bar, MinnionHealthBar, Transform::from_xyz(0., 15., 0.)));
-            });
-        }
-    }
-    
+    // Ready to attack right away
+    let mut attack_timer = MinnionAttackTimer {
+        timer: Timer::from_seconds(base.cooldown, TimerMode::Once),
+    };
+    attack_timer.timer.tick(Duration::from_secs_f32(base.cooldown));
+
+    let animation = Animation {
+            set: animation_set,
+            state: AnimationState::Idle,
+            timer: Timer::from_seconds(0.1, TimerMode::Repeating),
+            last_state: None
+        };
+
+    // Spawn Minion
+    let minnion_ent = commands.spawn((
+        Sprite {
+            color: base.tint,
+            ..Sprite::from_atlas_image(
+                idle_tex,
+                TextureAtlas { layout: anim_layout, index: 0 }
+            )
+        },
+        animation,
+        Transform::from_scale(Vec3::splat(4.0)).with_translation(position.extend(0.0)),
+        Minnion,
+        Collider {
+            radius: 22.,
+            layer: layers::MINION,
+            // Soldiers pass through each other, but not through orcs
+            mask: layers::ENEMY | layers::NPC,
+        },
+        Stats { hp: base.max_hp, max_hp: base.max_hp, attack: base.attack },
+        hit_timer,
+        MinnionMode::Neutral,
+        attack_timer,
+        // Sword thrust in front of melee units, archers shoot and healers don't fight
+        Hitboxes::for_states(
+            &base.attack_state.filter(|_| !base.ranged).into_iter().collect::<Vec<_>>(),
+            &[HitboxFrames {
+                first: 3,
+                last: 4,
+                shape: HitShape::Rect { offset: Vec2::new(40.0, 0.0), half_size: Vec2::new(35.0, 25.0) },
+            }],
+        ),
+        Hurtbox {
+            offset: Vec2::ZERO,
+            half_size: Vec2::new(32.0, 44.0),
+        },
+        Faction::Player,
+        Perception::new(500.0, 550.0, TargetPriority::Nearest, 0.5),
+        Navigator::default(),
+        // Fresh recruits join the player's escort
+        (Steering::default(), PlayerMinion(kind), Veterancy::default(), Defense { armor: base.armor, ..default() }, Stance::Follow),
+    )).id();
+
+    let hp_bar = Sprite {
+            color: Color::srgb(125.0, 0.0, 0.0),
+            custom_size: Some(Vec2::new(100. / 4., 1.0)),
+            ..default()
+        };
+        
+    commands.entity(minnion_ent)
+    .with_children(| parent | {
+        parent.spawn((hp_bar, MinnionHealthBar, Transform::from_xyz(0., 15., 0.)));
+    });
+
+    minnion_ent
 }
 
 
@@ -174,148 +275,147 @@ fn update_hp_bars(
 }
 
 
+/// Counts down the hurt reaction, a neutral minion that got hit turns aggressive.
+/// Death is handled by `core::combat`
 fn hit_reaction(
-    mut commands: Commands,
-    mut query: Query<(Entity, &Stats, &mut HitReactionTimer, &mut MinnionMode), With<Minnion>>,
+    mut query: Query<(&mut HitReactionTimer, &mut MinnionMode), With<Minnion>>,
     
     time: Res<Time>,
 ) {
-    for (enemy, stats, mut reaction_timer,mut mode) in query.iter_mut() {
+    for (mut reaction_timer,mut mode) in query.iter_mut() {
         reaction_timer.timer.tick(time.delta());
 
         if reaction_timer.timer.just_finished() && *mode == MinnionMode::Neutral {
             *mode = MinnionMode::Aggresiv;
         }
-        if stats.hp <= 0 {
-            commands.entity(enemy).despawn();
-            continue;
-        }
-    }
-}
-
-
-// Finds target for minnion 
-fn find_enemy_target(
-    minnions: Query<(Entity, &Transform, &MinnionMode), (With<Minnion>, (Without<Player>, Without<Enemy>, Without<Target>))>,
-    targets: Query<(Entity, &Transform), (With<Enemy>, Without<Minnion>, Without<Player>)>,
-    mut commands: Commands,
-) {
-    for (minnion, minnion_tf, mode) in minnions.iter() {
-        if *mode != MinnionMode::Aggresiv {
-            continue;
-        }
-        let mut closest_target: Option<(Entity, f32)> = None;
-
-        for (target, target_tf) in targets.iter() {
-            let dist = minnion_tf.translation.distance(target_tf.translation);
-            if dist < 500.0 {
-                match closest_target {
-                    Some((_, closest_dist)) if dist < closest_dist => {
-                        closest_target = Some((target, dist));
-                    }
-                    None => {
-                        closest_target = Some((target, dist));
-                    }
-                    _ => {}
-                }
-            }
-        }
-
-        if let Some((ent, _)) = closest_target {
-            commands.entity(minnion).insert(Target { target: ent });
-        } else {
-            commands.entity(minnion).remove::<Target>();
-        }
-    }
-}
-
-
-fn drop_target(
-    mut query: Query<(Entity, &mut Target, &Transform), With<Minnion>>,
-    targets: Query<&Transform>,
-    mut commands: Commands,
-) {
-    for (minnion_entity, target, tf) in query.iter_mut() {
-
-        match targets.get(target.target) {
-            Ok(target_tf) => {
-                if tf.translation.distance(target_tf.translation) > 550.0 {
-                    commands.entity(minnion_entity).remove::<Target>();
-                }
-            }
-            Err(_) => {
-                commands.entity(minnion_entity).remove::<Target>();
-            }
-        }
     }
 }
 
 
+/// Everything `move_minnions_tow_target` reads from a minnion
+type MinnionWalker<'a> = (
+    Entity,
+    &'a mut Transform,
+    &'a mut Navigator,
+    &'a mut Steering,
+    &'a StatusEffects,
+    Option<&'a Target>,
+    Option<&'a MoveTo>,
+    Option<&'a FlowFieldGoal>,
+    &'a CommandQueue,
+    &'a PlayerMinion,
+);
+
+/// Walks minnions to their `MoveTo` order or their target, around walls through the `Navigator`.
+/// Group orders follow the shared flow field of their `FlowFieldGoal` instead.
+/// Minnions holding position don't chase anything, archers stop once their target is in range.
+/// The resulting velocity goes to `Steering`.
 fn move_minnions_tow_target(
-    mut minnions: Query<(Entity, &mut Transform, &MinnionAttackTimer, Option<&Target>, Option<&mut MoveTo>), With<Minnion>>,
-    targets: Query<&Transform, (Without<Minnion>, Without<Player>)>,
-    time: Res<Time>,
+    mut minnions: Query<MinnionWalker, (With<Minnion>, Without<Dying>)>,
+    targets: Query<&GlobalTransform>,
+    grid: Option<Res<NavGrid>>,
+    flow_fields: Res<FlowFields>,
     mut commands: Commands
 ) {
-    for (mn, mut minnion_tf, attack_timer, maybe_target, maybe_mt) in minnions.iter_mut() {
+    for (mn, mut minnion_tf, mut navigator, mut steering, statuses, maybe_target, maybe_mt, maybe_flow, queue, minnion) in minnions.iter_mut() {
+        let base = profile(minnion.0);
         // If the enemy has a target assigned
        let move_loc: Option<Vec3> = maybe_mt
         .as_ref()
         .map(|mt| mt.loc)
         .or_else(|| {
-            maybe_target.and_then(|target| {
-                targets.get(target.target).ok().map(|tf| tf.translation)
+            maybe_target.filter(|_| !queue.holds()).and_then(|target| {
+                targets.get(target.target).ok().map(|tf| tf.translation())
             })
         });
 
+        let position = minnion_tf.translation.truncate();
+        // The shared field leads to the middle of the formation, the last stretch to the own slot is a path of its own
+        let flow_goal = maybe_flow
+            .filter(|_| maybe_mt.is_some_and(|mt| position.distance(mt.loc.truncate()) > FLOW_HANDOFF))
+            .map(|goal| goal.0);
+        navigator.set_goal(if flow_goal.is_some() { None } else { move_loc.map(|loc| loc.truncate()) });
+
+        let mut desired = Vec2::ZERO;
         if let Some(move_to) = move_loc {
-            if !attack_timer.timer.finished() {
-                let direction = (move_to - minnion_tf.translation).normalize_or_zero();
-                
-                // Movement towards the target
-                minnion_tf.translation += direction * time.delta_secs() * 100.0;
-
-                // Rotate the sprite towards the target
-                if direction.x.abs() > 0.1 {
-                    minnion_tf.scale.x = direction.x.signum() * 4.0;
-                }
+            let direction = match flow_goal {
+                // Straight at the goal until the field is ready
+                Some(goal) => grid
+                    .as_deref()
+                    .and_then(|grid| flow_fields.direction(grid, goal, position))
+                    .unwrap_or_else(|| (goal - position).normalize_or_zero()),
+                None => navigator.steer(position, 20.0),
+            };
+            
+            // Movement towards the target, slowing down when closing in
+            let remaining = position.distance(move_to.truncate());
+            let in_range = maybe_mt.is_none() && base.ranged && remaining <= base.range * RANGED_HOLD;
+            if !in_range {
+                desired = arrive(direction, base.speed * statuses.speed_multiplier(), remaining, 40.0);
+            }
+
+            // Rotate the sprite towards the target
+            if direction.x.abs() > 0.1 {
+                minnion_tf.scale.x = direction.x.signum() * 4.0;
             }
         }
+        steering.desired = desired;
+
         if let Some(mt) = maybe_mt {
-            if minnion_tf.translation.distance(mt.loc) < 30. {
-                commands.entity(mn).remove::<MoveTo>();
+            if minnion_tf.translation.distance(mt.loc) < ARRIVE_RADIUS {
+                commands.entity(mn).remove::<(MoveTo, FlowFieldGoal)>();
             }
         }
     } 
 }
 
 
+/// Everything `change_animation_state` reads from a minnion
+type MinnionLook<'a> = (
+    &'a HitReactionTimer,
+    &'a mut Animation,
+    &'a Sprite,
+    &'a mut MinnionAttackTimer,
+    &'a Transform,
+    Option<&'a Target>,
+    Option<&'a MoveTo>,
+    &'a MinnionMode,
+    &'a CommandQueue,
+    &'a PlayerMinion,
+);
+
+/// Picks the animation, getting hit interrupts everything. Melee units in range play
+/// one swing per attack cooldown and stand idle in between, archers keep drawing.
 fn change_animation_state(
-    q: Query<(&HitReactionTimer, &mut Animation, &Transform, Option<&Target>, Option<&MoveTo>, &MinnionMode), With<Minnion>>,
-    targets_q: Query<&Transform, (With<Enemy>, Without<Minnion>)>
+    q: Query<MinnionLook, (With<Minnion>, Without<Dying>)>,
+    targets_q: Query<&GlobalTransform>
 ) {
 
-    for (hit_timer,mut anim, tf,  maybe_target, maybe_mt, mode) in q {
+    for (hit_timer,mut anim, sprite, mut cooldown, tf,  maybe_target, maybe_mt, mode, queue, minnion) in q {
+        let base = profile(minnion.0);
         if !hit_timer.timer.finished() {
             anim.state = AnimationState::Hurt;
             continue;
         }
 
         if let Some(mt) = maybe_mt {
-            if tf.translation.distance(mt.loc) > 30. {
+            if tf.translation.distance(mt.loc) > ARRIVE_RADIUS {
                 anim.state = AnimationState::Walk;
                 continue;
             }
         }
 
-        if *mode == MinnionMode::Aggresiv {
+        if let Some(attack_state) = base.attack_state.filter(|_| fights(minnion.0, mode, queue)) {
             if let Some(target) = maybe_target {
                 if let Ok(target_tf) = targets_q.get(target.target) {
 
-                    if tf.translation.distance(target_tf.translation) < 110. {
-                        anim.state = AnimationState::Attack01;
-                        continue;
-                    } else {
+                    if tf.translation.distance(target_tf.translation()) < base.range {
+                        let swinging = swinging(&anim, sprite, attack_state);
+                        if base.ranged || swinging || start_swing(&mut cooldown.timer, swinging) {
+                            anim.state = attack_state;
+                            continue;
+                        }
+                    } else if !queue.holds() {
                         anim.state = AnimationState::Walk;
                         continue;
                     }
@@ -327,39 +427,77 @@ fn change_animation_state(
 }
 
 
+/// Ticks the attack cooldown. Melee damage is dealt by the `Hitboxes` of the attack animation,
+/// which `change_animation_state` only starts once the cooldown is up. Archers shoot in
+/// `shoot_arrows`, healers heal in `heal_allies`.
 fn attack(
-    mut enemy_q: Query<(Entity, &Transform, &mut MinnionAttackTimer, &HitReactionTimer, Option<&Target>, &MinnionMode), With<Minnion>>,
-    targets_q: Query<&Transform, With<Enemy>>,
+    mut enemy_q: Query<(&mut MinnionAttackTimer, &StatusEffects), (With<Minnion>, Without<Dying>)>,
     time: Res<Time>,
-    mut attack_events: EventWriter<AttackEvent>,
 ) {
-    for (enemy_ent, enemy_tf, mut cooldown, hit_timer, maybe_target, mode) in enemy_q.iter_mut() {
-        if *mode == MinnionMode::Aggresiv {
-            if let Some(target) = maybe_target {
-                cooldown.timer.tick(time.delta());
-                if let Ok(target_tf) = targets_q.get(target.target) {
-                    let dist = enemy_tf.translation.distance(target_tf.translation);
-
-                    if hit_timer.timer.finished() {
-                        if dist < 110.  {
-
-                            if cooldown.timer.just_finished() {
-                                attack_events.write(AttackEvent {
-                                    attacker: enemy_ent,
-                                    target: target.target,
-                                    damage: 30,
-                                });
-                                cooldown.timer.reset();
-                            }
-                        }
-                    }
-                }
-            }
-        }
+    for (mut cooldown, statuses) in enemy_q.iter_mut() {
+        cooldown.timer.tick(time.delta().mul_f32(statuses.attack_speed_multiplier()));
     }
 }
 
 
+/// Archers loose an arrow at their target whenever their attack cooldown is up
+fn shoot_arrows(
+    mut commands: Commands,
+    mut archers: Query<(Entity, &Transform, &mut MinnionAttackTimer, &Stats, &Faction, &PlayerMinion, Option<&Target>), Without<Dying>>,
+    targets: Query<&GlobalTransform>,
+) {
+    for (archer, tf, mut cooldown, stats, faction, minnion, maybe_target) in archers.iter_mut() {
+        let base = profile(minnion.0);
+        if !base.ranged || !cooldown.timer.finished() {
+            continue;
+        }
+        let Some(target_pos) = maybe_target.and_then(|target| targets.get(target.target).ok()).map(|tf| tf.translation()) else {
+            continue;
+        };
+        if tf.translation.distance(target_pos) > base.range {
+            continue;
+        }
+        cooldown.timer.reset();
+
+        launch_projectile(
+            &mut commands,
+            &arrow(stats.attack),
+            archer,
+            *faction,
+            tf.translation,
+            (target_pos - tf.translation).truncate(),
+            Sprite {
+                color: Color::srgb(0.6, 0.4, 0.2),
+                custom_size: Some(Vec2::new(24.0, 3.0)),
+                ..default()
+            },
+        );
+    }
+}
 
+/// Healers mend the most wounded ally in reach whenever their cooldown is up, by their attack
+fn heal_allies(
+    mut healers: Query<(Entity, &Transform, &mut MinnionAttackTimer, &PlayerMinion), Without<Dying>>,
+    mut allies: Query<(Entity, &Transform, &mut Stats, &Faction), Without<Dying>>,
+) {
+    for (healer, tf, mut cooldown, minnion) in healers.iter_mut() {
+        let base = profile(minnion.0);
+        if base.fights() || !cooldown.timer.finished() {
+            continue;
+        }
 
-
+        let wounded = allies
+            .iter()
+            .filter(|(.., faction)| **faction == Faction::Player)
+            .map(|(ally, ally_tf, stats, _)| (ally, ally_tf.translation.truncate(), stats.hp, stats.max_hp));
+        let Some(ally) = most_wounded(wounded, tf.translation.truncate(), base.range) else {
+            continue;
+        };
+        cooldown.timer.reset();
+        // Heals grow with rank, like attacks
+        let amount = allies.get(healer).map_or(base.attack, |(_, _, stats, _)| stats.attack);
+        if let Ok((_, _, mut stats, _)) = allies.get_mut(ally) {
+            stats.hp = (stats.hp + amount).min(stats.max_hp);
+        }
+    }
+}
@@ -1,2 +1,7 @@
 pub mod minnion;
-pub mod control;
\ No newline at end of file
+pub mod control;
+pub mod formation;
+pub mod command;
+pub mod recruit;
+pub mod unit;
+pub mod stance;
\ No newline at end of file
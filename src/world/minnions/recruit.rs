@@ -0,0 +1,209 @@
+use std::{collections::VecDeque, time::Duration};
+use bevy::prelude::*;
+use crate::{
+    core::common::{Dying, GameState, MinionType},
+    player::player::PlayerGoodies,
+    world::{minnions::{minnion::{spawn_minnion, Minnion}, unit::MINION_TYPES}, npc::{track_npc_in_range, NpcInRange}},
+};
+
+/// Plugin for recruiting minnions at barracks, paid from `PlayerGoodies::money`
+pub struct RecruitPlugin;
+
+impl Plugin for RecruitPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Population>()
+            .init_resource::<RecruitChoice>()
+            .add_systems(
+                Update,
+                (choose_recruit.after(track_npc_in_range), recruit, train_recruits)
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+/// Most minnions the player can lead at once, recruits still in training included
+#[derive(Resource)]
+pub struct Population {
+    pub cap: usize,
+}
+
+impl Default for Population {
+    fn default() -> Self {
+        Self { cap: 20 }
+    }
+}
+
+/// Type recruited next, picked with `Tab` at a barracks
+#[derive(Resource, Default)]
+pub struct RecruitChoice(usize);
+
+impl RecruitChoice {
+    pub fn kind(&self) -> MinionType {
+        MINION_TYPES[self.0]
+    }
+}
+
+/// Price and training time of a minnion type
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RecruitOffer {
+    pub cost: u32,
+    pub secs: f32,
+}
+
+/// What the barracks ask for a minnion of `kind`
+pub fn offer(kind: MinionType) -> RecruitOffer {
+    match kind {
+        MinionType::Soldier => RecruitOffer { cost: 50, secs: 3.0 },
+        MinionType::Archer => RecruitOffer { cost: 70, secs: 4.0 },
+        MinionType::ShieldBearer => RecruitOffer { cost: 80, secs: 5.0 },
+        MinionType::Healer => RecruitOffer { cost: 90, secs: 5.0 },
+    }
+}
+
+/// Why a recruit was turned down
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RecruitError {
+    NotEnoughMoney,
+    PopulationCap,
+}
+
+/// Whether the player can pay `cost` and still has room for one more minnion
+pub fn check_recruit(money: u32, cost: u32, population: usize, cap: usize) -> Result<(), RecruitError> {
+    if population >= cap {
+        Err(RecruitError::PopulationCap)
+    } else if money < cost {
+        Err(RecruitError::NotEnoughMoney)
+    } else {
+        Ok(())
+    }
+}
+
+/// Trains recruits one after another. Finished ones appear at `spawn_point`, relative to the barracks.
+#[derive(Component)]
+pub struct Barracks {
+    pub spawn_point: Vec2,
+    training: VecDeque<MinionType>,
+    timer: Timer,
+}
+
+impl Barracks {
+    pub fn new(spawn_point: Vec2) -> Self {
+        Self { spawn_point, training: VecDeque::new(), timer: Timer::default() }
+    }
+
+    /// Queues a recruit, training starts right away if nobody else is in training
+    pub fn enqueue(&mut self, kind: MinionType) {
+        if self.training.is_empty() {
+            self.timer = Timer::from_seconds(offer(kind).secs, TimerMode::Once);
+        }
+        self.training.push_back(kind);
+    }
+
+    /// Recruits waiting or in training
+    pub fn queued(&self) -> usize {
+        self.training.len()
+    }
+
+    /// Advances the training, returns the recruit that finished
+    pub fn tick(&mut self, delta: Duration) -> Option<MinionType> {
+        if self.training.is_empty() || !self.timer.tick(delta).finished() {
+            return None;
+        }
+
+        let done = self.training.pop_front();
+        if let Some(next) = self.training.front() {
+            self.timer = Timer::from_seconds(offer(*next).secs, TimerMode::Once);
+        }
+        done
+    }
+}
+
+/// `Tab` next to a barracks switches the type to recruit
+fn choose_recruit(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    in_range: Res<NpcInRange>,
+    q_barracks: Query<(), With<Barracks>>,
+    mut choice: ResMut<RecruitChoice>,
+) {
+    if keyboard.just_pressed(KeyCode::Tab) && in_range.npc().is_some_and(|npc| q_barracks.contains(npc)) {
+        choice.0 = (choice.0 + 1) % MINION_TYPES.len();
+        let offer = offer(choice.kind());
+        info!("Recruiting {:?} for {} money", choice.kind(), offer.cost);
+    }
+}
+
+/// `R` next to a barracks recruits the chosen type
+fn recruit(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    in_range: Res<NpcInRange>,
+    choice: Res<RecruitChoice>,
+    population: Res<Population>,
+    mut goodies: ResMut<PlayerGoodies>,
+    mut q_barracks: Query<&mut Barracks>,
+    q_minnions: Query<(), (With<Minnion>, Without<Dying>)>,
+) {
+    if !keyboard.just_pressed(KeyCode::KeyR) {
+        return;
+    }
+
+    let training: usize = q_barracks.iter().map(Barracks::queued).sum();
+    let Some(mut barracks) = in_range.npc().and_then(|npc| q_barracks.get_mut(npc).ok()) else {
+        return;
+    };
+
+    let kind = choice.kind();
+    let offer = offer(kind);
+    match check_recruit(goodies.money, offer.cost, q_minnions.iter().count() + training, population.cap) {
+        Ok(()) => {
+            goodies.money -= offer.cost;
+            barracks.enqueue(kind);
+            info!("Recruiting a {:?}, {} in training", kind, barracks.queued());
+        }
+        Err(err) => info!("Can't recruit a {:?}: {:?}", kind, err),
+    }
+}
+
+/// Spawns recruits once their training is done
+fn train_recruits(
+    mut commands: Commands,
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    mut q_barracks: Query<(&Transform, &mut Barracks)>,
+) {
+    for (tf, mut barracks) in q_barracks.iter_mut() {
+        if let Some(kind) = barracks.tick(time.delta()) {
+            let position = tf.translation.truncate() + barracks.spawn_point;
+            spawn_minnion(&mut commands, &asset_server, &mut texture_atlas_layouts, kind, position);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recruits_need_money_and_room() {
+        assert_eq!(check_recruit(100, 50, 3, 20), Ok(()));
+        assert_eq!(check_recruit(40, 50, 3, 20), Err(RecruitError::NotEnoughMoney));
+        assert_eq!(check_recruit(100, 50, 20, 20), Err(RecruitError::PopulationCap));
+    }
+
+    #[test]
+    fn recruits_train_one_after_another() {
+        let mut barracks = Barracks::new(Vec2::ZERO);
+        let secs = offer(MinionType::Soldier).secs;
+        barracks.enqueue(MinionType::Soldier);
+        barracks.enqueue(MinionType::Soldier);
+
+        assert_eq!(barracks.tick(Duration::from_secs_f32(secs / 2.0)), None);
+        assert_eq!(barracks.tick(Duration::from_secs_f32(secs / 2.0)), Some(MinionType::Soldier));
+        assert_eq!(barracks.queued(), 1);
+        // The next one starts from scratch
+        assert_eq!(barracks.tick(Duration::from_secs_f32(secs / 2.0)), None);
+        assert_eq!(barracks.tick(Duration::from_secs_f32(secs / 2.0)), Some(MinionType::Soldier));
+        assert_eq!(barracks.tick(Duration::from_secs_f32(secs)), None);
+    }
+}
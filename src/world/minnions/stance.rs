@@ -0,0 +1,163 @@
+use std::f32::consts::PI;
+use bevy::prelude::*;
+use crate::{
+    core::{common::{Dying, MoveTo, Player, Target}, targeting::Perception},
+    world::minnions::{command::CommandQueue, minnion::Minnion},
+};
+
+/// Idle followers further than this from their escort spot walk back to it
+const ESCORT_RADIUS: f32 = 180.0;
+/// Escort spots lie within this distance of the player
+const ESCORT_SPREAD: f32 = 110.0;
+/// Escort spots before the spiral starts over
+const ESCORT_SLOTS: u32 = 16;
+/// Followers give up a chase this far from their escort spot
+const FOLLOW_LEASH: f32 = 450.0;
+/// Guards further than this from their anchor walk back once the fight is over
+const GUARD_RADIUS: f32 = 30.0;
+/// Guards give up a chase this far from their anchor
+const GUARD_LEASH: f32 = 300.0;
+/// Units walking home are back this close to their spot
+const RETURNED: f32 = 20.0;
+/// The walk to a moving escort spot is redirected once the spot moved this far
+const REDIRECT: f32 = 40.0;
+
+/// What a minnion does once it has no orders left
+#[derive(Component, Default, Clone, Copy, PartialEq, Debug)]
+pub enum Stance {
+    /// Stays wherever it is
+    #[default]
+    Free,
+    /// Keeps a loose escort around the player
+    Follow,
+    /// Stays around `anchor` and returns there after fighting
+    Guard { anchor: Vec2 },
+}
+
+/// Minnion that chased too far and walks back home, it ignores enemies until it's there
+#[derive(Component)]
+pub struct Returning;
+
+/// Where a unit with a stance belongs and how far it may stray
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Home {
+    pub spot: Vec2,
+    /// Idle units further away walk back
+    pub radius: f32,
+    /// Chasing units further away give up
+    pub leash: f32,
+}
+
+impl Stance {
+    /// Home of the unit in escort spot `slot`, `None` for free units and followers without a player
+    pub fn home(self, slot: u32, player: Option<Vec2>) -> Option<Home> {
+        match self {
+            Stance::Free => None,
+            Stance::Follow => player.map(|player| Home {
+                spot: escort_spot(player, slot),
+                radius: ESCORT_RADIUS,
+                leash: FOLLOW_LEASH,
+            }),
+            Stance::Guard { anchor } => Some(Home { spot: anchor, radius: GUARD_RADIUS, leash: GUARD_LEASH }),
+        }
+    }
+}
+
+/// Spot `slot` of the escort around the player, spread out on a sunflower spiral
+pub fn escort_spot(player: Vec2, slot: u32) -> Vec2 {
+    let slot = slot % ESCORT_SLOTS;
+    // Golden angle, neighbouring slots end up on opposite sides
+    let angle = slot as f32 * PI * (3.0 - 5f32.sqrt());
+    let radius = ESCORT_SPREAD * ((slot as f32 + 0.5) / ESCORT_SLOTS as f32).sqrt();
+    player + Vec2::from_angle(angle) * radius
+}
+
+/// Everything `keep_stance` reads from a minnion
+type Stanced<'a> = (
+    Entity,
+    &'a Stance,
+    &'a CommandQueue,
+    &'a mut Perception,
+    &'a Transform,
+    Option<&'a MoveTo>,
+    Option<&'a Target>,
+    Has<Returning>,
+);
+
+/// Keeps minnions without orders near their home: idle ones walk back to it, ones chasing
+/// an enemy past the leash drop it and return. Followers take the escort spots in order,
+/// so they stay packed around the player. Runs after `run_commands`, orders come first.
+pub fn keep_stance(
+    mut commands: Commands,
+    mut minnions: Query<Stanced, (With<Minnion>, Without<Dying>)>,
+    q_player: Query<&Transform, (With<Player>, Without<Minnion>)>,
+) {
+    let player = q_player.single().ok().map(|tf| tf.translation.truncate());
+
+    let mut followers: Vec<Entity> = minnions
+        .iter()
+        .filter(|(_, stance, queue, ..)| **stance == Stance::Follow && queue.current().is_none())
+        .map(|(mn, ..)| mn)
+        .collect();
+    // Sorted, so every follower keeps its spot from frame to frame
+    followers.sort();
+
+    for (mn, stance, queue, mut perception, transform, maybe_mt, maybe_target, mut returning) in minnions.iter_mut() {
+        let slot = followers.binary_search(&mn).unwrap_or_default() as u32;
+        let home = stance.home(slot, player).filter(|_| queue.current().is_none());
+        let Some(home) = home else {
+            if returning {
+                commands.entity(mn).remove::<Returning>();
+            }
+            continue;
+        };
+
+        let position = transform.translation.truncate();
+        let distance = position.distance(home.spot);
+        if maybe_target.is_some() && distance > home.leash {
+            commands.entity(mn).remove::<Target>().insert(Returning);
+            returning = true;
+        }
+        if returning {
+            perception.active = false;
+            if distance <= RETURNED {
+                commands.entity(mn).remove::<Returning>();
+            }
+        }
+
+        let idle = maybe_target.is_none() || returning;
+        let heading_home = maybe_mt.is_some_and(|mt| mt.loc.truncate().distance(home.spot) <= REDIRECT);
+        if idle && !heading_home && (returning || distance > home.radius || maybe_mt.is_some()) {
+            commands.entity(mn).insert(MoveTo { loc: home.spot.extend(0.0) });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escort_spots_spread_around_the_player() {
+        let player = Vec2::new(100.0, -50.0);
+        let spots: Vec<Vec2> = (0..ESCORT_SLOTS).map(|slot| escort_spot(player, slot)).collect();
+
+        assert!(spots.iter().all(|spot| spot.distance(player) <= ESCORT_SPREAD));
+        for (i, a) in spots.iter().enumerate() {
+            assert!(spots[i + 1..].iter().all(|b| a.distance(*b) > 10.0));
+        }
+        assert_eq!(escort_spot(player, ESCORT_SLOTS + 3), spots[3]);
+    }
+
+    #[test]
+    fn stances_pick_their_home() {
+        let anchor = Vec2::new(10.0, 20.0);
+
+        assert_eq!(Stance::Free.home(0, Some(Vec2::ZERO)), None);
+        assert_eq!(Stance::Follow.home(0, None), None);
+        assert_eq!(Stance::Guard { anchor }.home(0, None).map(|home| home.spot), Some(anchor));
+        let follow = Stance::Follow.home(2, Some(Vec2::ZERO)).unwrap();
+        assert_eq!(follow.spot, escort_spot(Vec2::ZERO, 2));
+        assert!(follow.leash > follow.radius);
+    }
+}
@@ -0,0 +1,211 @@
+use bevy::prelude::*;
+use crate::{
+    core::common::{AnimationState, Died, MinionType, PlayerMinion, Stats},
+    world::minnions::minnion::MinnionHealthBar,
+};
+
+/// Plugin for minnion veterancy: kills give XP, ranks raise max HP and attack
+pub struct UnitPlugin;
+
+impl Plugin for UnitPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (gain_xp, show_rank).chain());
+    }
+}
+
+/// XP a minnion gets for every kill
+const KILL_XP: u32 = 50;
+/// XP needed for each rank
+const RANK_XP: [u32; 3] = [100, 300, 600];
+/// Max HP and attack gained per rank, as a fraction of the type's base value
+const RANK_BONUS: f32 = 0.15;
+
+/// Every type, in the order the barracks offer them
+pub const MINION_TYPES: [MinionType; 4] = [MinionType::Soldier, MinionType::Archer, MinionType::ShieldBearer, MinionType::Healer];
+
+/// Everything that sets one minnion type apart
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct UnitProfile {
+    pub max_hp: i32,
+    /// Damage per hit, for healers the HP mended per heal
+    pub attack: i32,
+    /// See `Defense::armor`
+    pub armor: i32,
+    pub speed: f32,
+    /// Distance at which it attacks its target, for healers how far their heals reach
+    pub range: f32,
+    /// Seconds between attacks or heals
+    pub cooldown: f32,
+    /// Shoots arrows from `range` instead of walking up to its target
+    pub ranged: bool,
+    /// Attack animation, `None` for units that never fight
+    pub attack_state: Option<AnimationState>,
+    pub tint: Color,
+}
+
+impl UnitProfile {
+    pub fn fights(&self) -> bool {
+        self.attack_state.is_some()
+    }
+}
+
+pub fn profile(kind: MinionType) -> UnitProfile {
+    match kind {
+        MinionType::Soldier => UnitProfile {
+            max_hp: 100,
+            attack: 25,
+            armor: 0,
+            speed: 100.0,
+            range: 110.0,
+            cooldown: 0.6,
+            ranged: false,
+            attack_state: Some(AnimationState::Attack01),
+            tint: Color::WHITE,
+        },
+        MinionType::Archer => UnitProfile {
+            max_hp: 70,
+            attack: 18,
+            armor: 0,
+            speed: 100.0,
+            range: 350.0,
+            cooldown: 1.2,
+            ranged: true,
+            attack_state: Some(AnimationState::Attack03),
+            tint: Color::srgb(0.7, 1.0, 0.7),
+        },
+        // Slow and hard to kill, holds the line for the others
+        MinionType::ShieldBearer => UnitProfile {
+            max_hp: 180,
+            attack: 12,
+            armor: 60,
+            speed: 80.0,
+            range: 110.0,
+            cooldown: 0.9,
+            ranged: false,
+            attack_state: Some(AnimationState::Attack02),
+            tint: Color::srgb(0.7, 0.8, 1.0),
+        },
+        MinionType::Healer => UnitProfile {
+            max_hp: 80,
+            attack: 15,
+            armor: 0,
+            speed: 90.0,
+            range: 250.0,
+            cooldown: 1.5,
+            ranged: false,
+            attack_state: None,
+            tint: Color::srgb(1.0, 0.9, 0.6),
+        },
+    }
+}
+
+/// Experience of a minnion, ranks raise its stats above its type's base
+#[derive(Component, Default)]
+pub struct Veterancy {
+    pub xp: u32,
+    pub rank: u32,
+}
+
+/// Rank reached with `xp`
+pub fn rank_for(xp: u32) -> u32 {
+    RANK_XP.iter().filter(|need| xp >= **need).count() as u32
+}
+
+/// A base stat raised by the bonus of `rank`
+pub fn ranked(base: i32, rank: u32) -> i32 {
+    (base as f32 * (1.0 + RANK_BONUS * rank as f32)).round() as i32
+}
+
+/// Colour of the health bar at each rank
+fn rank_color(rank: u32) -> Color {
+    match rank {
+        0 => Color::srgb(125.0, 0.0, 0.0),
+        1 => Color::srgb(0.8, 0.5, 0.2),
+        2 => Color::srgb(0.8, 0.8, 0.85),
+        _ => Color::srgb(1.0, 0.85, 0.0),
+    }
+}
+
+/// Most wounded ally within `range` of `from`, by the fraction of HP missing.
+/// Allies are given as (entity, position, hp, max hp).
+pub fn most_wounded(allies: impl IntoIterator<Item = (Entity, Vec2, i32, i32)>, from: Vec2, range: f32) -> Option<Entity> {
+    allies
+        .into_iter()
+        .filter(|(_, position, hp, max_hp)| hp < max_hp && position.distance(from) <= range)
+        .min_by(|a, b| (a.2 as f32 / a.3 as f32).total_cmp(&(b.2 as f32 / b.3 as f32)))
+        .map(|(ally, ..)| ally)
+}
+
+/// Gives the killer XP and promotes it once it has enough. Promotion heals by the HP gained.
+fn gain_xp(
+    mut died: EventReader<Died>,
+    mut q_minnions: Query<(&PlayerMinion, &mut Veterancy, &mut Stats)>,
+) {
+    for event in died.read() {
+        let Some(Ok((minnion, mut veterancy, mut stats))) = event.killer.map(|killer| q_minnions.get_mut(killer)) else {
+            continue;
+        };
+
+        veterancy.xp += KILL_XP;
+        let rank = rank_for(veterancy.xp);
+        if rank > veterancy.rank {
+            let base = profile(minnion.0);
+            let max_hp = ranked(base.max_hp, rank);
+            stats.hp += max_hp - stats.max_hp;
+            stats.max_hp = max_hp;
+            stats.attack = ranked(base.attack, rank);
+            veterancy.rank = rank;
+            info!("{:?} promoted to rank {}", minnion.0, rank);
+        }
+    }
+}
+
+/// Colours the health bar by rank
+fn show_rank(
+    q_minnions: Query<(&Veterancy, &Children), Changed<Veterancy>>,
+    mut bars: Query<&mut Sprite, With<MinnionHealthBar>>,
+) {
+    for (veterancy, children) in q_minnions.iter() {
+        for child in children.iter() {
+            if let Ok(mut sprite) = bars.get_mut(child) {
+                sprite.color = rank_color(veterancy.rank);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranks_follow_xp() {
+        assert_eq!(rank_for(0), 0);
+        assert_eq!(rank_for(99), 0);
+        assert_eq!(rank_for(100), 1);
+        assert_eq!(rank_for(300), 2);
+        assert_eq!(rank_for(10_000), 3);
+    }
+
+    #[test]
+    fn ranks_raise_stats() {
+        assert_eq!(ranked(100, 0), 100);
+        assert_eq!(ranked(100, 1), 115);
+        assert_eq!(ranked(20, 3), 29);
+    }
+
+    #[test]
+    fn healers_pick_the_most_wounded_in_reach() {
+        let (a, b, c, d) = (Entity::from_raw(1), Entity::from_raw(2), Entity::from_raw(3), Entity::from_raw(4));
+        let allies = [
+            (a, Vec2::new(50.0, 0.0), 60, 100),
+            // Worse off, but out of reach
+            (b, Vec2::new(500.0, 0.0), 10, 100),
+            (c, Vec2::new(0.0, 50.0), 90, 200),
+            (d, Vec2::ZERO, 100, 100),
+        ];
+
+        assert_eq!(most_wounded(allies, Vec2::ZERO, 100.0), Some(c));
+        assert_eq!(most_wounded([(d, Vec2::ZERO, 100, 100)], Vec2::ZERO, 100.0), None);
+    }
+}
@@ -1,12 +1,13 @@
 use bevy::{platform::collections::HashMap, prelude::*,};
 
-use crate::{core::common::{Item, Player}, player::player::PlayerGoodies, DialogWindow};
+use crate::{core::collision::detect_contacts, core::common::{layers, Body, BodyKind, Collider, CollisionEnded, CollisionStarted, Faction, Item, Player, Sensor}, player::player::PlayerGoodies, world::minnions::recruit::Barracks, DialogWindow};
 
-/// NPC roles (can be either a general NPC or a shopkeeper)
+/// NPC roles (a general NPC, a shopkeeper or a barracks recruiting minnions)
 #[derive(PartialEq, Clone)]
 pub enum NpcRole {
     Shop,
     Npc,
+    Barracks,
 }
 
 /// Marker component for identifying the dialog UI of a shop
@@ -20,6 +21,25 @@ pub struct NpcOffers {
 }
 
 
+/// Sensor around an NPC, the player can talk to the NPC while standing inside it
+#[derive(Component)]
+pub struct InteractionZone;
+
+/// NPC whose interaction zone the player is currently standing in
+#[derive(Resource, Default)]
+pub struct NpcInRange(Option<Entity>);
+
+impl NpcInRange {
+    pub fn npc(&self) -> Option<Entity> {
+        self.0
+    }
+}
+
+/// Radius of the NPC interaction zone
+const INTERACTION_RADIUS: f32 = 150.0;
+/// Where recruits appear, relative to their barracks
+const RECRUIT_SPAWN_POINT: Vec2 = Vec2::new(0.0, -120.0);
+
 #[derive(Component)]
 pub struct ShopItemButton {
     item: Item,
@@ -48,15 +68,20 @@ impl Plugin for NpcPlugin {
     fn build(&self, app: &mut App) {
         app
             // Load and spawn NPCs on startup
+            .init_resource::<NpcInRange>()
             .add_systems(Startup, (load_npcs, spawn_npcs).chain())
-            // Update system for shop interaction
-            .add_systems(Update, (npc_shop_interaction, shop_item_click_system, refresh_shop_ui_system, shop_auto_close_system));
+            // Update system for shop interaction, reading this frame's `NpcInRange`
+            .add_systems(Update, (
+                track_npc_in_range.after(detect_contacts),
+                (npc_shop_interaction, shop_item_click_system, refresh_shop_ui_system, shop_auto_close_system).chain(),
+            ).chain());
     }
 }
 
 /// Loads NPC definitions and inserts them as a resource
 fn load_npcs(mut commands: Commands) {
-    let npc_list = HashMap::from([(
+    let npc_list = HashMap::from([
+    (
         String::from("Zdzichu"),
         Npc {
             name: String::from("Zdzichu"),
@@ -71,7 +96,18 @@ fn load_npcs(mut commands: Commands) {
                 ]),
             }),
         },
-    )]);
+    ),
+    (
+        String::from("Barracks"),
+        Npc {
+            name: String::from("Barracks"),
+            loc: Vec3 { x: 400., y: 0., z: 3. },
+            sprite_path: String::from("Player/Sprites/IDLE/idle_down.png"),
+            role: NpcRole::Barracks,
+            offer: None,
+        },
+    ),
+    ]);
 
     commands.insert_resource(NpcList { list: npc_list });
 }
@@ -109,6 +145,26 @@ fn spawn_npcs(
                 ..default()
             },
             npc_data.clone(), // Clone the Npc component and attach it
+            // Units bump into NPCs, the player can walk through them to reach the shop
+            Collider {
+                radius: 25.0,
+                layer: layers::NPC,
+                mask: layers::MINION | layers::ENEMY,
+            },
+            // Shopkeeper stays in place no matter who bumps into him
+            Body { mass: 1.0, kind: BodyKind::Static },
+            Faction::Neutral,
+            // Zone the player has to stand in to talk to the NPC
+            children![(
+                InteractionZone,
+                Sensor,
+                Collider {
+                    radius: INTERACTION_RADIUS,
+                    layer: layers::NPC,
+                    mask: layers::PLAYER,
+                },
+                Transform::default(),
+            )],
         ))
         .id();
 
@@ -116,29 +172,50 @@ fn spawn_npcs(
         if let Some(offers) = &npc_data.offer {
             commands.entity(entity).insert(offers.clone());
         }
+        if npc_data.role == NpcRole::Barracks {
+            commands.entity(entity).insert(Barracks::new(RECRUIT_SPAWN_POINT));
+        }
+    }
+}
+
+/// Keeps `NpcInRange` up to date from the player entering and leaving interaction zones
+pub fn track_npc_in_range(
+    mut started: EventReader<CollisionStarted>,
+    mut ended: EventReader<CollisionEnded>,
+    q_zone: Query<&ChildOf, With<InteractionZone>>,
+    q_player: Query<Entity, With<Player>>,
+    mut in_range: ResMut<NpcInRange>,
+) {
+    let Ok(player) = q_player.single() else {
+        return;
+    };
+
+    for event in ended.read() {
+        if let Some(zone) = event.other(player).and_then(|zone| q_zone.get(zone).ok()) {
+            if in_range.0 == Some(zone.parent()) {
+                in_range.0 = None;
+            }
+        }
+    }
+    for event in started.read() {
+        if let Some(zone) = event.other(player).and_then(|zone| q_zone.get(zone).ok()) {
+            in_range.0 = Some(zone.parent());
+        }
     }
 }
 
 /// System that triggers when player presses `E` near an NPC with offers
 fn npc_shop_interaction(
     keyboard: Res<ButtonInput<KeyCode>>,
-    q_npc: Query<(Entity, &Transform, Option<&NpcOffers>), With<Npc>>,
-    q_shop: Query<Entity, With<ShopDialog>>,
-    q_player: Query<&Transform, With<Player>>,
+    in_range: Res<NpcInRange>,
     mut diag_window: ResMut<DialogWindow>,
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
 ) {
     // Trigger only when E key is just pressed
     if keyboard.just_pressed(KeyCode::KeyE) {
-        for p_tf in q_player.iter() {
-            for (npc, npc_tf, maybe_offers) in q_npc.iter() {
-                // Check proximity between player and NPC
-                if p_tf.translation.distance(npc_tf.translation) < 150.0 {
-                    diag_window.open = true;
-                    diag_window.current_shop_npc = Some(npc);
-                }
-            }
+        // Player has to stand in the NPC's interaction zone
+        if let Some(npc) = in_range.0 {
+            diag_window.open = true;
+            diag_window.current_shop_npc = Some(npc);
         }
     }
 }
@@ -267,10 +344,10 @@ fn refresh_shop_ui_system(
 }
 
 
+/// Closes the shop once the player walks out of the NPC's interaction zone
 fn shop_auto_close_system(
     mut diag_window: ResMut<DialogWindow>,
-    q_player: Query<&Transform, With<Player>>,
-    q_npc: Query<&Transform, With<Npc>>,
+    in_range: Res<NpcInRange>,
     q_shop: Query<Entity, With<ShopDialog>>,
     mut commands: Commands,
 ) {
@@ -278,20 +355,13 @@ fn shop_auto_close_system(
         return;
     }
 
-    let Ok(player_tf) = q_player.single() else {
-        return;
-    };
-
     if let Some(npc_entity) = diag_window.current_shop_npc {
-        if let Ok(npc_tf) = q_npc.get(npc_entity) {
-            let distance = player_tf.translation.distance(npc_tf.translation);
-            if distance > 150.0 {
-                diag_window.open = false;
-                diag_window.current_shop_npc = None;
-
-                if let Ok(shop_dialog) = q_shop.single() {
-                    commands.entity(shop_dialog).despawn();
-                }
+        if in_range.0 != Some(npc_entity) {
+            diag_window.open = false;
+            diag_window.current_shop_npc = None;
+
+            if let Ok(shop_dialog) = q_shop.single() {
+                commands.entity(shop_dialog).despawn();
             }
         }
     }